@@ -0,0 +1,12 @@
+//! plays or watches a game on a server from the terminal:
+//! `cargo run -p live --bin client -- 127.0.0.1:7878 [--spectate]`
+
+fn main(){
+    let mut args = std::env::args().skip(1);
+    let address = args.next().unwrap_or("127.0.0.1:7878".to_string());
+    let player = args.next().as_deref() != Some("--spectate");
+    if let Err(error) = live::net::run_client(&address, player) {
+        eprintln!("{address}: {error}");
+        std::process::exit(1);
+    }
+}
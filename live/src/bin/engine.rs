@@ -0,0 +1,10 @@
+//! the built-in engine behind the text protocol described in `protocol.rs`, for
+//! other programs to play against: `cargo run --release -p live --bin engine`
+
+fn main(){
+    let stdin = std::io::stdin();
+    if let Err(error) = live::protocol::run_engine(stdin.lock(), std::io::stdout()) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,10 @@
+//! serves one two player game on the network and prints its record when it is over:
+//! `cargo run -p live --bin server -- 127.0.0.1:7878`
+
+fn main(){
+    let address = std::env::args().nth(1).unwrap_or("127.0.0.1:7878".to_string());
+    if let Err(error) = live::net::run_server(&address) {
+        eprintln!("{address}: {error}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,10 @@
+//! solves a reduced piece set and checks the engine against it:
+//! `cargo run --release -p live --bin tablebase -- rgby/13/1 --verify 1000`
+
+fn main(){
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(error) = live::tablebase::run(&args) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
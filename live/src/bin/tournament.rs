@@ -0,0 +1,10 @@
+//! round robin between engine configurations without a window:
+//! `cargo run --release -p live --no-default-features --bin tournament -- default eval_weights.txt`
+
+fn main(){
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(error) = live::tournament::run(&args) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,205 @@
+/// where the clock gets the current time from, in seconds
+pub trait TimeSource{
+    fn now(&self)->f64;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeControl{
+    /// `increment` seconds are added after every finished turn
+    Increment{
+        initial: f64,
+        increment: f64,
+    },
+    /// the first `delay` seconds of every turn are not charged
+    Delay{
+        initial: f64,
+        delay: f64,
+    },
+}
+
+/// `increment 300 5` or `delay 300 5`: the time each player starts with and
+/// the increment or delay, in seconds
+impl std::str::FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = ||format!("expected `increment` or `delay` and two numbers of seconds, found `{}`", text.trim());
+        let [mode, initial, extra] = text.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(error());
+        };
+        let seconds = |word: &str|word.parse::<f64>().ok().filter(|seconds|seconds.is_finite() && *seconds >= 0.0).ok_or_else(error);
+        let (initial, extra) = (seconds(initial)?, seconds(extra)?);
+        match mode{
+            "increment" => Ok(TimeControl::Increment { initial, increment: extra }),
+            "delay" => Ok(TimeControl::Delay { initial, delay: extra }),
+            _ => Err(error()),
+        }
+    }
+}
+
+/// chess-style clock with one timer per player, only the player to move loses time
+#[derive(Debug, Clone)]
+pub struct Clock{
+    control: TimeControl,
+    remaining: Vec<f64>,
+    /// the player whose timer runs and when their turn started
+    running: Option<(u8, f64)>,
+    flagged: Option<u8>,
+}
+
+impl Clock{
+    pub fn new(control: TimeControl, player_count: u8)->Self{
+        let initial = match control{
+            TimeControl::Increment { initial, .. } |
+            TimeControl::Delay { initial, .. } => initial,
+        };
+        Clock {
+            control,
+            remaining: vec![initial; player_count as usize],
+            running: None,
+            flagged: None,
+        }
+    }
+
+    /// time charged for a turn that has lasted `elapsed` seconds
+    fn charge(&self, elapsed: f64)->f64{
+        match self.control{
+            TimeControl::Increment { .. } => elapsed,
+            TimeControl::Delay { delay, .. } => (elapsed - delay).max(0.0),
+        }
+    }
+
+    /// time `player` has left, counting the turn in progress
+    pub fn remaining(&self, player: u8, time: &impl TimeSource)->f64{
+        let stored = self.remaining[player as usize];
+        match self.running{
+            Some((running, start)) if running == player => stored - self.charge(time.now() - start),
+            _ => stored,
+        }
+    }
+
+    /// the player who ran out of time, the clock stops when that happens
+    pub fn flagged(&self)->Option<u8>{
+        self.flagged
+    }
+
+    fn flag(&mut self, player: u8){
+        self.remaining[player as usize] = 0.0;
+        self.running = None;
+        self.flagged = Some(player);
+    }
+
+    /// call once per frame with `Board::current_player`,
+    /// switches timers when the player to move changed, see `flagged`
+    pub fn update(&mut self, current_player: u8, time: &impl TimeSource){
+        if self.flagged.is_some() {
+            return;
+        }
+        let now = time.now();
+        match self.running{
+            None => {
+                self.running = Some((current_player, now));
+            },
+            Some((player, start)) if player != current_player => {
+                let left = self.remaining[player as usize] - self.charge(now - start);
+                if left <= 0.0 {
+                    self.flag(player);
+                    return;
+                }
+                let increment = match self.control{
+                    TimeControl::Increment { increment, .. } => increment,
+                    TimeControl::Delay { .. } => 0.0,
+                };
+                self.remaining[player as usize] = left + increment;
+                self.running = Some((current_player, now));
+            },
+            Some((player, _)) => {
+                if self.remaining(player, time) <= 0.0 {
+                    self.flag(player);
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::cell::Cell;
+
+    struct FakeTime(Cell<f64>);
+    impl FakeTime{
+        fn advance(&self, seconds: f64){
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+    impl TimeSource for FakeTime{
+        fn now(&self)->f64{
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn only_the_player_to_move_loses_time(){
+        let time = FakeTime(Cell::new(100.0));
+        let mut clock = Clock::new(TimeControl::Increment { initial: 60.0, increment: 0.0 }, 2);
+        clock.update(0, &time);
+        time.advance(10.0);
+        assert_eq!(clock.remaining(0, &time), 50.0);
+        assert_eq!(clock.remaining(1, &time), 60.0);
+
+        clock.update(1, &time);
+        time.advance(5.0);
+        assert_eq!(clock.remaining(0, &time), 50.0);
+        assert_eq!(clock.remaining(1, &time), 55.0);
+    }
+
+    #[test]
+    fn increment_is_added_after_the_turn(){
+        let time = FakeTime(Cell::new(0.0));
+        let mut clock = Clock::new(TimeControl::Increment { initial: 60.0, increment: 2.0 }, 2);
+        clock.update(0, &time);
+        time.advance(10.0);
+        clock.update(1, &time);
+        assert_eq!(clock.remaining(0, &time), 52.0);
+    }
+
+    #[test]
+    fn delay_is_not_charged(){
+        let time = FakeTime(Cell::new(0.0));
+        let mut clock = Clock::new(TimeControl::Delay { initial: 60.0, delay: 5.0 }, 2);
+        clock.update(0, &time);
+        time.advance(3.0);
+        assert_eq!(clock.remaining(0, &time), 60.0);
+        time.advance(4.0);
+        assert_eq!(clock.remaining(0, &time), 58.0);
+        clock.update(1, &time);
+        assert_eq!(clock.remaining(0, &time), 58.0);
+    }
+
+    #[test]
+    fn time_controls_read_from_text(){
+        assert_eq!("increment 300 5".parse(), Ok(TimeControl::Increment { initial: 300.0, increment: 5.0 }));
+        assert_eq!(" delay 60 2.5\n".parse(), Ok(TimeControl::Delay { initial: 60.0, delay: 2.5 }));
+        for bad in ["", "delay 60", "bronstein 60 5", "delay -1 5", "increment 60 five", "delay 60 5 5"]{
+            assert!(bad.parse::<TimeControl>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn running_out_of_time_flags_and_stops(){
+        let time = FakeTime(Cell::new(0.0));
+        let mut clock = Clock::new(TimeControl::Increment { initial: 10.0, increment: 5.0 }, 2);
+        clock.update(0, &time);
+        assert_eq!(clock.flagged(), None);
+        time.advance(11.0);
+        clock.update(0, &time);
+        assert_eq!(clock.flagged(), Some(0));
+        // switching afterwards neither revives player 0 nor starts player 1
+        clock.update(1, &time);
+        assert_eq!(clock.flagged(), Some(0));
+        time.advance(100.0);
+        assert_eq!(clock.remaining(0, &time), 0.0);
+        assert_eq!(clock.remaining(1, &time), 10.0);
+    }
+}
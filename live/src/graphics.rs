@@ -58,6 +58,11 @@ impl<'a> DrawHandle<'a> {
             ffi::DrawCircleV(center.into(), radius, color)
         }
     }
+    pub fn draw_line(&mut self, start: Pos2, end: Pos2, thickness: f32, color: Color){
+        unsafe{
+            ffi::DrawLineEx(start.into(), end.into(), thickness, color)
+        }
+    }
     pub fn draw_circle_lines(&mut self, center: Pos2, radius: f32, color: Color){
         unsafe{
             ffi::DrawRingLines(center.into(), radius, radius, 0.0, 360.0, 36, color);
@@ -1,26 +1,133 @@
 use std::collections::HashSet;
 
+mod position;
+#[cfg(feature = "window")]
+mod threats;
+mod blunder;
+mod setup;
+mod graph;
+mod powers;
+mod game;
+mod eval;
+mod record;
+mod book;
+mod rules;
+mod ai;
+mod snapshot;
+#[cfg(feature = "window")]
+mod correspondence;
+mod retrograde;
+mod pieces;
+mod narration;
+#[cfg(feature = "window")]
+mod help;
+
+pub(crate) use powers::Powers;
+pub(crate) use game::{Game, Decision};
+pub(crate) use eval::Weights;
+pub(crate) use book::Book;
+#[cfg(feature = "window")]
+pub(crate) use book::Continuation;
+pub(crate) use ai::{Engine, Limits, Search};
+#[cfg(feature = "window")]
+pub(crate) use correspondence::Correspondence;
+pub(crate) use retrograde::{Tablebase, Value};
+pub(crate) use pieces::PieceSet;
+#[cfg(feature = "window")]
+pub(crate) use narration::Narration;
+#[cfg(feature = "window")]
+pub(crate) use help::RuleHelp;
+#[cfg(feature = "window")]
+pub(crate) use threats::Threats;
+pub(crate) use record::{Record, format_turn, parse_turn, piece_code};
+
+#[cfg(feature = "window")]
+pub(crate) use setup::HomeworldAdvice;
+
 /// uniquely identifies a piece in the game
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-struct Id(u8);
+pub(crate) struct Id(u8);
+
+/// sizes a piece set can have, see `PieceSet`
+const SIZE_SLOTS: usize = 4;
+
+/// copies of each color and size a piece set can have
+const COPY_SLOTS: usize = 3;
 
-#[derive(Debug, Clone, Copy)]
-enum Color{
+/// number of distinct color and size combinations, valid kinds are `0..KIND_COUNT`
+const KIND_COUNT: usize = 4 * SIZE_SLOTS;
+
+/// number of ids any piece set can use, valid ids are `0..ID_COUNT`.
+/// The standard set has 36 of them.
+const ID_COUNT: usize = KIND_COUNT * COPY_SLOTS;
+
+impl Id{
+    pub(crate) fn new(color: Color, size: Size, copy: u8)->Id{
+        Id(((color as usize * SIZE_SLOTS + size as usize) * COPY_SLOTS) as u8 + copy)
+    }
+    /// ids are laid out as `(color * SIZE_SLOTS + size) * COPY_SLOTS + copy`
+    fn kind(self)->usize{
+        self.0 as usize / COPY_SLOTS
+    }
+    /// which of the pieces of its color and size this is, from 0
+    fn copy(self)->u8{
+        (self.0 as usize % COPY_SLOTS) as u8
+    }
+    pub(crate) fn color(self)->Color{
+        match self.kind() / SIZE_SLOTS {
+            0 => Color::Red,
+            1 => Color::Green,
+            2 => Color::Blue,
+            _ => Color::Yellow,
+        }
+    }
+    pub(crate) fn size(self)->Size{
+        match self.kind() % SIZE_SLOTS {
+            0 => Size::Small,
+            1 => Size::Medium,
+            2 => Size::Large,
+            _ => Size::Huge,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Color{
     Red = 0,
     Green = 1,
     Blue = 2,
     Yellow = 3,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Size{
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Size{
     Small = 0,
     Medium = 1,
     Large = 2,
+    /// only in piece sets with a fourth size
+    Huge = 3,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Action{
+const COLORS: [Color; 4] = [Color::Red, Color::Green, Color::Blue, Color::Yellow];
+const SIZES: [Size; SIZE_SLOTS] = [Size::Small, Size::Medium, Size::Large, Size::Huge];
+
+/// `small red`, `large yellow`, ...
+impl std::fmt::Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = format!("{:?}", self.size()).to_lowercase();
+        let color = format!("{:?}", self.color()).to_lowercase();
+        write!(f, "{size} {color}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Action{
+    /// the current player takes two stars and a ship from the bank as their homeworld,
+    /// only while they have none
+    CreateHomeworld{
+        stars: [Id; 2],
+        ship: Id,
+    },
     CreateShip{
         ship: Id,
         star: Id,
@@ -34,110 +141,619 @@ enum Action{
         start_star: Id,
         end_star: Id,
     },
+    /// takes `star` from the bank and moves `ship` there, a system is never created empty
     CreateStar{
         star: Id,
+        ship: Id,
+        start_star: Id,
     },
     DestroyStar{
         star: Id,
     },
+    /// the current player takes over another player's `ship`
+    CaptureShip{
+        ship: Id,
+        star: Id,
+    },
+    /// every star and ship of `color` in the system goes back to the bank,
+    /// only when there are at least four of them
+    Catastrophe{
+        star: Id,
+        color: Color,
+    },
 }
 
-#[derive(Debug, Clone)]
-struct Star{
-    star: Id,
-    ships: Box<[Vec<Id>]>,
+
+/// a star system, either a homeworld or one discovered during the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct System{
+    /// a single star, homeworlds start with two
+    pub(crate) stars: Vec<Id>,
+    /// the player whose homeworld this is
+    pub(crate) home: Option<u8>,
+    /// ships of every player, indexed by player
+    pub(crate) ships: Box<[Vec<Id>]>,
 }
-#[derive(Debug, Clone)]
-struct HomeWorld{
-    stars: Vec<Id>,
-    ships: Box<[Vec<Id>]>,
+
+/// handle to a system that stays valid until that system is destroyed,
+/// a handle to a destroyed system never refers to a different one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct SystemId{
+    index: u16,
+    generation: u16,
 }
 
-#[derive(Debug, Clone)]
-struct Board{
+impl System{
+    /// ships can travel between systems whose stars have no size in common
+    fn connected(&self, other: &System)->bool{
+        self.stars.iter().all(|a|other.stars.iter().all(|b|a.size() != b.size()))
+    }
+    /// colors of the stars and of `player`'s ships here, whether or not
+    /// the player has a ship to act with
+    fn powers(&self, player: u8)->Powers{
+        self.stars.iter().chain(&self.ships[player as usize]).map(|id|id.color()).collect()
+    }
+    /// stars and ships of `color`, four or more of them cause a catastrophe
+    fn color_count(&self, color: Color)->usize{
+        self.stars.iter().chain(self.ships.iter().flatten()).filter(|id|id.color() == color).count()
+    }
+}
+
+/// the generation of a slot that was reused so often it is never used again,
+/// so that old handles can't wrap around to a new system
+const RETIRED: u16 = u16::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Slot{
+    generation: u16,
+    system: Option<System>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Board{
     current_player: u8,
     player_count: u8,
+    /// the pieces this game is played with
+    set: PieceSet,
     bank: HashSet<Id>,
-    stars: Vec<Star>,
-    homeworlds: Box<[HomeWorld]>,
+    /// free slots are reused with the next generation, until it runs out
+    systems: Vec<Slot>,
 }
 
-enum Either<A,B>{
-    A(A),
-    B(B),
+/// a broken invariant found by `Board::validate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BoardError{
+    UnknownPiece(Id),
+    MissingPiece(Id),
+    DuplicatePiece(Id),
+    /// a non-home system without any ships
+    EmptySystem(Id),
+    /// a system does not have exactly one ship list per player
+    ShipLists,
+    /// a system without stars, a non-home system with two stars or a homeworld with more
+    StarCount,
+    /// a player with more than one homeworld
+    Homeworlds,
 }
 
 
 impl Board{
-    fn star_mut(&mut self, star: Id)->Option<Either<&mut Star, &mut HomeWorld>>{
-        if let Some(hw) = self.homeworlds.iter_mut().find(|hw|hw.stars.contains(&star)){
-            Some(Either::B(hw))
-        }else if let Ok(i) = self.stars.binary_search_by_key(&star, |system|system.star){
-            Some(Either::A(&mut self.stars[i]))
+    /// every piece of the standard set in the bank, nobody has a homeworld yet
+    pub(crate) fn new(player_count: u8)->Self{
+        Self::with_set(player_count, PieceSet::STANDARD)
+    }
+    /// `new` for a game played with another piece set
+    pub(crate) fn with_set(player_count: u8, set: PieceSet)->Self{
+        Board {
+            current_player: 0,
+            player_count,
+            set,
+            bank: set.ids().collect(),
+            systems: Vec::new(),
+        }
+    }
+    #[cfg(feature = "window")]
+    pub(crate) fn set(&self)->PieceSet{
+        self.set
+    }
+    pub(crate) fn current_player(&self)->u8{
+        self.current_player
+    }
+    pub(crate) fn player_count(&self)->u8{
+        self.player_count
+    }
+    /// hands the turn to the next player
+    pub(crate) fn end_turn(&mut self){
+        self.current_player = (self.current_player + 1) % self.player_count;
+    }
+
+    pub(crate) fn system(&self, id: SystemId)->Option<&System>{
+        let slot = self.systems.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.system.as_ref()
+    }
+    fn system_mut(&mut self, id: SystemId)->Option<&mut System>{
+        let slot = self.systems.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.system.as_mut()
+    }
+    pub(crate) fn systems(&self)->impl Iterator<Item = (SystemId, &System)>{
+        self.systems.iter().enumerate().filter_map(|(i, slot)|{
+            let id = SystemId { index: i as u16, generation: slot.generation };
+            slot.system.as_ref().map(|system|(id, system))
+        })
+    }
+    /// the system `star` belongs to
+    fn find_system(&self, star: Id)->Option<SystemId>{
+        self.systems().find(|(_, system)|system.stars.contains(&star)).map(|(id, _)|id)
+    }
+    fn homeworld(&self, player: u8)->Option<SystemId>{
+        self.systems().find(|(_, system)|system.home == Some(player)).map(|(id, _)|id)
+    }
+    fn insert_system(&mut self, system: System)->SystemId{
+        if let Some(i) = self.systems.iter().position(|slot|slot.system.is_none() && slot.generation < RETIRED){
+            let slot = &mut self.systems[i];
+            slot.system = Some(system);
+            SystemId { index: i as u16, generation: slot.generation }
         }else{
-            None
+            self.systems.push(Slot { generation: 0, system: Some(system) });
+            SystemId { index: self.systems.len() as u16 - 1, generation: 0 }
+        }
+    }
+    /// frees the slot, handles to it stop resolving
+    fn remove_system(&mut self, id: SystemId)->Option<System>{
+        self.system(id)?;
+        let slot = &mut self.systems[id.index as usize];
+        // a slot in use is below `RETIRED`, so this never wraps
+        slot.generation += 1;
+        slot.system.take()
+    }
+
+    /// checks that every piece is in exactly one place and that the
+    /// systems are laid out the way `apply_action` expects
+    fn validate(&self)->Result<(), BoardError>{
+        let mut seen = [0u8; ID_COUNT];
+        let set = self.set;
+        let mut see = |id: Id|{
+            let count = seen.get_mut(id.0 as usize)
+                .filter(|_|set.contains(id))
+                .ok_or(BoardError::UnknownPiece(id))?;
+            *count += 1;
+            if *count > 1 {
+                return Err(BoardError::DuplicatePiece(id));
+            }
+            Ok(())
+        };
+        for id in &self.bank{
+            see(*id)?;
+        }
+        for (_, system) in self.systems(){
+            for star in &system.stars{
+                see(*star)?;
+            }
+            for ship in system.ships.iter().flatten(){
+                see(*ship)?;
+            }
+        }
+        if let Some(id) = self.set.ids().find(|id|seen[id.0 as usize] == 0){
+            return Err(BoardError::MissingPiece(id));
+        }
+
+        let player_count = self.player_count as usize;
+        let mut homes = vec![0; player_count];
+        for (_, system) in self.systems(){
+            if system.ships.len() != player_count {
+                return Err(BoardError::ShipLists);
+            }
+            let max_stars = match system.home{
+                Some(player) => {
+                    *homes.get_mut(player as usize).ok_or(BoardError::Homeworlds)? += 1;
+                    2
+                },
+                None => 1,
+            };
+            if system.stars.is_empty() || system.stars.len() > max_stars {
+                return Err(BoardError::StarCount);
+            }
+            if system.home.is_none() && system.ships.iter().all(|ships|ships.is_empty()){
+                return Err(BoardError::EmptySystem(system.stars[0]));
+            }
+        }
+        if homes.iter().any(|count|*count > 1){
+            return Err(BoardError::Homeworlds);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn apply_action(self, action: Action) -> Option<Self>{
+        let board = self.apply_action_unchecked(action)?;
+        #[cfg(debug_assertions)]
+        if let Err(err) = board.validate(){
+            panic!("{action:?} broke the board: {err:?}");
         }
+        Some(board)
     }
-    fn apply_action(mut self, action: Action) -> Option<Self>{
+
+    fn apply_action_unchecked(mut self, action: Action) -> Option<Self>{
         let player_i = self.current_player as usize;
         match action{
+            Action::CreateHomeworld { stars, ship } => {
+                if self.homeworld(self.current_player).is_some() {
+                    return None;
+                }
+                let stars = vec![self.bank.take(&stars[0])?, self.bank.take(&stars[1])?];
+                let mut ships = vec![Vec::new(); self.player_count as usize];
+                ships[player_i].push(self.bank.take(&ship)?);
+                self.insert_system(System { stars, home: Some(self.current_player), ships: ships.into_boxed_slice() });
+            },
             Action::CreateShip { ship, star } => {
                 let ship = self.bank.take(&ship)?;
-                match self.star_mut(star)? {
-                    Either::A(star) => {
-                        star.ships[player_i].push(ship);
-                    },
-                    Either::B(hw) => {
-                        hw.ships[player_i].push(ship);
-                    },
-                }
+                let system = self.find_system(star)?;
+                self.system_mut(system)?.ships[player_i].push(ship);
             },
             Action::DestroyShip { ship, star } => {
-                let ships = match self.star_mut(star)?{
-                    Either::A(star) => &mut star.ships[player_i],
-                    Either::B(hw) => &mut hw.ships[player_i],
-                };
-                let i = ships.iter()
-                    .enumerate()
-                    .find_map(|(i,sh)|if *sh == ship {Some(i)}else{None})?;
+                let id = self.find_system(star)?;
+                let system = self.system_mut(id)?;
+                let ships = &mut system.ships[player_i];
+                let i = ships.iter().position(|sh|*sh == ship)?;
                 let ship = ships.remove(i);
+
+                // a system nobody occupies anymore goes back to the bank
+                let abandoned = system.home.is_none() && system.ships.iter().all(|ships|ships.is_empty());
                 self.bank.insert(ship);
+                if abandoned {
+                    let system = self.remove_system(id)?;
+                    self.bank.extend(system.stars);
+                }
             },
             Action::MoveShip { ship, start_star, end_star } => {
-                self = self.apply_action(Action::DestroyShip { ship, star: start_star })?;
-                self = self.apply_action(Action::CreateShip { ship, star: end_star })?;
+                self = self.apply_action_unchecked(Action::DestroyShip { ship, star: start_star })?;
+                self = self.apply_action_unchecked(Action::CreateShip { ship, star: end_star })?;
             },
-            Action::CreateStar { star } => {
+            Action::CreateStar { star, ship, start_star } => {
                 let star = self.bank.take(&star)?;
-                self.stars.push(Star { star, ships: Default::default() })
+                let ships = vec![Vec::new(); self.player_count as usize].into_boxed_slice();
+                self.insert_system(System { stars: vec![star], home: None, ships });
+                self = self.apply_action_unchecked(Action::MoveShip { ship, start_star, end_star: star })?;
             },
             Action::DestroyStar { star } => {
-                if let Ok(i) = self.stars.binary_search_by_key(&star, |system|system.star){
-                    let star = self.stars.remove(i);
-                    for player_ships in star.ships.into_iter(){
-                        for ship in player_ships{
-                            self.bank.insert(*ship);
-                        }
-                    }
-                    self.bank.insert(star.star);
-                }else if let Some(hw) =
-                    self.homeworlds.iter_mut().find(|hw|hw.stars.contains(&star))
-                {
-                    let i = hw.stars.iter()
-                        .enumerate()
-                        .find_map(|(i,st)|if *st == star {Some(i)}else{None})?;
-                    hw.stars.remove(i);
-                    self.bank.insert(star);
-                    if hw.stars.is_empty(){
-                        for player_ships in hw.ships.into_iter(){
-                            for ship in player_ships{
-                                self.bank.insert(*ship);
-                            }
-                        }
-                    }
+                let id = self.find_system(star)?;
+                let system = self.system_mut(id)?;
+                system.stars.retain(|st|*st != star);
+                let starless = system.stars.is_empty();
+                self.bank.insert(star);
+                if starless {
+                    let system = self.remove_system(id)?;
+                    self.bank.extend(system.ships.iter().flatten());
+                }
+            },
+            Action::CaptureShip { ship, star } => {
+                let id = self.find_system(star)?;
+                let system = self.system_mut(id)?;
+                let owner = system.ships.iter().position(|ships|ships.contains(&ship)).filter(|owner|*owner != player_i)?;
+                system.ships[owner].retain(|sh|*sh != ship);
+                system.ships[player_i].push(ship);
+            },
+            Action::Catastrophe { star, color } => {
+                let id = self.find_system(star)?;
+                let system = self.system_mut(id)?;
+                if system.color_count(color) < 4 {
+                    return None;
+                }
+                let mut removed: Vec<Id> = Vec::new();
+                for pieces in std::iter::once(&mut system.stars).chain(system.ships.iter_mut()){
+                    removed.extend(pieces.iter().filter(|id|id.color() == color));
+                    pieces.retain(|id|id.color() != color);
+                }
+                let gone = system.stars.is_empty()
+                    || system.home.is_none() && system.ships.iter().all(|ships|ships.is_empty());
+                self.bank.extend(removed);
+                if gone {
+                    let system = self.remove_system(id)?;
+                    self.bank.extend(system.stars.iter().chain(system.ships.iter().flatten()));
                 }
             },
         }
         Some(self)
     }
 }
+
+/// xorshift64, so the same seed plays the same game everywhere
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+impl Rng{
+    pub(crate) fn new(seed: u64)->Self{
+        Rng(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+    }
+    fn next(&mut self)->u64{
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+    pub(crate) fn below(&mut self, n: usize)->usize{
+        (self.next() % n as u64) as usize
+    }
+    /// uniform in `0.0..1.0`
+    pub(crate) fn unit(&mut self)->f32{
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    #[cfg(test)]
+    pub(crate) fn pick<T: Copy>(&mut self, items: &[T])->T{
+        items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::position::Position;
+
+    fn take_random(bank: &mut HashSet<Id>, rng: &mut Rng)->Id{
+        let mut ids: Vec<Id> = bank.iter().copied().collect();
+        ids.sort();
+        let id = rng.pick(&ids);
+        bank.remove(&id);
+        id
+    }
+
+    /// piece id of the first copy of a color and size
+    pub(super) fn piece(color: Color, size: Size)->Id{
+        Id::new(color, size, 0)
+    }
+
+    /// two player board with `systems` and everything else in the bank
+    pub(super) fn board(systems: Vec<System>)->Board{
+        let mut board = Board::new(2);
+        for system in systems{
+            for id in system.stars.iter().chain(system.ships.iter().flatten()){
+                board.bank.remove(id);
+            }
+            board.insert_system(system);
+        }
+        assert_eq!(board.validate(), Ok(()));
+        board
+    }
+
+    pub(super) fn system(stars: Vec<Id>, home: Option<u8>, ships: [Vec<Id>; 2])->System{
+        System { stars, home, ships: Box::new(ships) }
+    }
+
+    /// both homeworlds with a small and a medium star, and a large and a
+    /// medium ship each
+    pub(super) fn two_homes()->Board{
+        use {Color::*, Size::*};
+        board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large), piece(Yellow, Medium)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Large), piece(Red, Medium)]]),
+        ])
+    }
+
+    /// every player gets two random home stars and one ship there
+    pub(super) fn random_start(player_count: u8, rng: &mut Rng)->Board{
+        let mut board = Board::new(player_count);
+        let mut bank = std::mem::take(&mut board.bank);
+        for player in 0..player_count{
+            let stars = vec![take_random(&mut bank, rng), take_random(&mut bank, rng)];
+            let mut ships = vec![Vec::new(); player_count as usize];
+            ships[player as usize].push(take_random(&mut bank, rng));
+            board.insert_system(System { stars, home: Some(player), ships: ships.into_boxed_slice() });
+        }
+        board.bank = bank;
+        board
+    }
+
+    /// one star id per system, usable as the `star` of an action
+    fn systems(board: &Board)->Vec<Id>{
+        board.systems().map(|(_, system)|system.stars[0]).collect()
+    }
+
+    /// every action `apply_action` should accept for the current player
+    pub(super) fn legal_actions(board: &Board)->Vec<Action>{
+        let player_i = board.current_player as usize;
+        let mut bank: Vec<Id> = board.bank.iter().copied().collect();
+        bank.sort();
+        let systems = systems(board);
+        let own_ships = board.systems()
+            .flat_map(|(_, system)|system.ships[player_i].iter().map(|ship|(*ship, system.stars[0])));
+
+        let mut actions = Vec::new();
+        for &star in &systems{
+            actions.push(Action::DestroyStar { star });
+            for &ship in &bank{
+                actions.push(Action::CreateShip { ship, star });
+            }
+        }
+        for (ship, star) in own_ships{
+            actions.push(Action::DestroyShip { ship, star });
+            for &end_star in systems.iter().filter(|end|**end != star){
+                actions.push(Action::MoveShip { ship, start_star: star, end_star });
+            }
+            for &new_star in &bank{
+                actions.push(Action::CreateStar { star: new_star, ship, start_star: star });
+            }
+        }
+        for (_, system) in board.systems(){
+            let star = system.stars[0];
+            for (_, ships) in system.ships.iter().enumerate().filter(|(owner, _)|*owner != player_i){
+                for &ship in ships{
+                    actions.push(Action::CaptureShip { ship, star });
+                }
+            }
+            for color in COLORS.into_iter().filter(|color|system.color_count(*color) >= 4){
+                actions.push(Action::Catastrophe { star, color });
+            }
+        }
+        actions
+    }
+
+    fn piece_count(board: &Board)->usize{
+        board.bank.len()
+        + board.systems()
+            .map(|(_, system)|system.stars.len() + system.ships.iter().map(Vec::len).sum::<usize>())
+            .sum::<usize>()
+    }
+
+    /// plays a random game, checking the invariants after every step
+    fn random_game(seed: u64, steps: usize)->Board{
+        let mut rng = Rng::new(seed);
+        let player_count = 2 + rng.below(3) as u8;
+        let mut board = random_start(player_count, &mut rng);
+        assert_eq!(board.validate(), Ok(()));
+
+        for step in 0..steps{
+            board.current_player = rng.below(player_count as usize) as u8;
+            let actions = legal_actions(&board);
+            if actions.is_empty(){
+                break;
+            }
+            let action = rng.pick(&actions);
+            assert_eq!(parse_turn(&format_turn(&[action])), Some(vec![action]), "seed {seed}, step {step}: {action:?}");
+            let before = board.clone();
+            board = board.apply_action(action)
+                .unwrap_or_else(||panic!("seed {seed}, step {step}: legal {action:?} was rejected"));
+            assert_eq!(board.validate(), Ok(()), "seed {seed}, step {step}: {action:?}");
+            assert_eq!(piece_count(&board), board.set.len(), "seed {seed}, step {step}: {action:?}");
+
+            // the action made on the position leads where the board went, and undoing it goes back
+            let mut position = Position::from(&before);
+            let undo = position.make(action).unwrap_or_else(||panic!("seed {seed}, step {step}: {action:?} was rejected"));
+            // a capture takes from whoever is next in turn order with that kind
+            if !matches!(action, Action::CaptureShip { .. }) {
+                assert_eq!(position, Position::from(&board), "seed {seed}, step {step}: {action:?}");
+            }
+            position.unmake(undo);
+            assert_eq!(position, Position::from(&before), "seed {seed}, step {step}: {action:?}");
+            // every action changes the board, and with it the position and the checksum
+            assert_ne!(Position::from(&board), position, "seed {seed}, step {step}: {action:?}");
+            // going through text is slow, every tenth game is enough
+            if seed.is_multiple_of(10) {
+                assert_ne!(board.checksum(), before.checksum(), "seed {seed}, step {step}: {action:?}");
+                let parsed = Board::parse(&board.to_string()).unwrap_or_else(||panic!("seed {seed}, step {step}: {board}"));
+                assert_eq!(parsed.checksum(), board.checksum(), "seed {seed}, step {step}: {action:?}");
+                assert_eq!(Position::from(&parsed), Position::from(&board), "seed {seed}, step {step}: {action:?}");
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn random_games_keep_invariants(){
+        for seed in 0..2000{
+            random_game(seed, 100);
+        }
+    }
+
+    #[test]
+    fn random_games_are_reproducible(){
+        for seed in 0..50{
+            assert_eq!(random_game(seed, 100), random_game(seed, 100));
+        }
+    }
+
+    #[test]
+    fn worn_out_slots_are_retired(){
+        let mut board = random_start(2, &mut Rng::new(1));
+        let empty = ||System { stars: Vec::new(), home: None, ships: vec![Vec::new(); 2].into_boxed_slice() };
+        let first = board.insert_system(empty());
+        let mut id = first;
+        for _ in 1..RETIRED{
+            board.remove_system(id).unwrap();
+            id = board.insert_system(empty());
+            assert_eq!(id.index, first.index);
+        }
+        board.remove_system(id).unwrap();
+        let next = board.insert_system(empty());
+        assert_ne!(next.index, first.index);
+        assert!(board.system(first).is_none());
+    }
+
+    #[test]
+    fn rejected_actions_are_not_applied(){
+        let mut rng = Rng::new(1);
+        let board = random_start(2, &mut rng);
+        let home = board.system(board.homeworld(1).unwrap()).unwrap();
+        let ship = home.ships[1][0];
+        let star = home.stars[0];
+        // player 0 does not own that ship
+        assert!(board.clone().apply_action(Action::DestroyShip { ship, star }).is_none());
+        // ships can only be created from the bank
+        assert!(board.clone().apply_action(Action::CreateShip { ship, star }).is_none());
+    }
+
+    #[test]
+    fn homeworlds_are_created_once(){
+        use {Color::*, Size::*};
+        let board = Board::new(2);
+        let action = Action::CreateHomeworld { stars: [Id::new(Red, Small, 0), Id::new(Green, Small, 1)], ship: Id::new(Yellow, Large, 2) };
+        let board = board.apply_action(action).unwrap();
+        let home = board.system(board.homeworld(0).unwrap()).unwrap();
+        assert_eq!(home.stars, vec![Id::new(Red, Small, 0), Id::new(Green, Small, 1)]);
+        assert_eq!(home.ships[0], vec![Id::new(Yellow, Large, 2)]);
+        assert!(board.clone().apply_action(Action::CreateHomeworld { stars: [Id::new(Red, Small, 1), Id::new(Green, Small, 2)], ship: Id::new(Yellow, Large, 1) }).is_none());
+
+        let mut board = board;
+        board.end_turn();
+        // pieces already in play can't be taken
+        assert!(board.clone().apply_action(Action::CreateHomeworld { stars: [Id::new(Red, Small, 0), Id::new(Green, Small, 2)], ship: Id::new(Yellow, Large, 1) }).is_none());
+        assert!(board.apply_action(Action::CreateHomeworld { stars: [Id::new(Red, Small, 1), Id::new(Green, Small, 2)], ship: Id::new(Yellow, Large, 1) }).is_some());
+    }
+
+    #[test]
+    fn handles_survive_other_systems(){
+        let mut rng = Rng::new(4);
+        let board = random_start(2, &mut rng);
+        let home = board.homeworld(0).unwrap();
+        let star = board.system(home).unwrap().stars[0];
+        let ship = board.system(home).unwrap().ships[0][0];
+        let new_star = *board.bank.iter().min().unwrap();
+
+        let board = board.apply_action(Action::CreateStar { star: new_star, ship, start_star: star }).unwrap();
+        let discovered = board.find_system(new_star).unwrap();
+        assert_eq!(board.system(home).unwrap().stars[0], star);
+
+        // moving the only ship back abandons the discovered system
+        let board = board.apply_action(Action::MoveShip { ship, start_star: new_star, end_star: star }).unwrap();
+        assert!(board.system(discovered).is_none());
+        assert!(board.bank.contains(&new_star));
+        assert_eq!(board.system(home).unwrap().ships[0], vec![ship]);
+
+        // the freed slot is reused without reviving the old handle
+        let board = board.apply_action(Action::CreateStar { star: new_star, ship, start_star: star }).unwrap();
+        assert!(board.system(discovered).is_none());
+        assert!(board.find_system(new_star).is_some());
+    }
+
+    #[test]
+    fn validate_finds_broken_boards(){
+        let mut rng = Rng::new(2);
+        let board = random_start(2, &mut rng);
+
+        let mut missing = board.clone();
+        let id = *missing.bank.iter().next().unwrap();
+        missing.bank.remove(&id);
+        assert_eq!(missing.validate(), Err(BoardError::MissingPiece(id)));
+
+        let mut duplicate = board.clone();
+        let home_0 = duplicate.homeworld(0).unwrap();
+        let home_1 = duplicate.homeworld(1).unwrap();
+        let ship = duplicate.system(home_0).unwrap().ships[0][0];
+        duplicate.system_mut(home_1).unwrap().ships[1].push(ship);
+        assert_eq!(duplicate.validate(), Err(BoardError::DuplicatePiece(ship)));
+
+        let mut empty = board.clone();
+        let star = take_random(&mut empty.bank, &mut rng);
+        empty.insert_system(System { stars: vec![star], home: None, ships: vec![Vec::new(); 2].into_boxed_slice() });
+        assert_eq!(empty.validate(), Err(BoardError::EmptySystem(star)));
+
+        let mut two_homes = board.clone();
+        two_homes.system_mut(home_1).unwrap().home = Some(0);
+        assert_eq!(two_homes.validate(), Err(BoardError::Homeworlds));
+    }
+}
@@ -0,0 +1,382 @@
+use super::*;
+use super::book::Book;
+use super::eval::Weights;
+use super::retrograde::Tablebase;
+use super::position::Position;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use std::time::{Duration, Instant};
+
+/// what winning or losing on the spot is worth next to evaluation scores
+pub(super) const DECISIVE: f32 = 1000.0;
+/// leaving a reply that wins on the spot, see `Position::refuted`
+const BLUNDER: f32 = 100.0;
+
+/// when a search has to stop and play the best turn it found so far.
+/// Listing the legal turns comes first and isn't cut short, neither is
+/// the first turn scored. A turn looked at deeper is dropped when time
+/// runs out in the middle of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Limits{
+    pub(crate) time: Option<Duration>,
+    /// how many turns to score at most, over all depths
+    pub(crate) turns: Option<usize>,
+    /// turns to look ahead, ours being the first. Without it the search goes
+    /// deeper while there is time, or stays at one turn without a time limit.
+    pub(crate) depth: Option<u8>,
+}
+
+/// how deep a search with time to spare goes at most
+const MAX_DEPTH: u8 = 6;
+
+/// the result of `Engine::search`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Search{
+    pub(crate) turn: Vec<Action>,
+    /// the turn's score for the player who takes it
+    pub(crate) score: f32,
+    /// how many turns were looked at
+    pub(crate) scored: usize,
+    /// how many there were to look at
+    pub(crate) total: usize,
+    /// turns looked ahead, the last depth may not have scored every turn
+    pub(crate) depth: u8,
+    /// `turn` and the replies the search expects, the principal variation
+    pub(crate) pv: Vec<Vec<Action>>,
+    /// positions evaluated
+    pub(crate) nodes: usize,
+    /// the turn came from the book
+    pub(crate) book: bool,
+    /// the turn came from the tablebase and its score is exact
+    pub(crate) solved: bool,
+}
+
+impl Search{
+    /// `turn` without a search behind it
+    fn found(turn: Vec<Action>, score: f32)->Self{
+        Search { pv: vec![turn.clone()], turn, score, scored: 0, total: 0, depth: 0, nodes: 0, book: false, solved: false }
+    }
+}
+
+/// a computer player that takes the turn leading to the best evaluation,
+/// looking one turn ahead or more with time to spare
+#[derive(Debug, Clone)]
+pub(crate) struct Engine{
+    pub(crate) weights: Weights,
+    /// every turn's score gets a random bonus up to this, so games vary
+    pub(crate) noise: f32,
+    /// book moves are played when they were seen in at least this many games
+    pub(crate) book_games: u32,
+    /// solved positions, played perfectly when the board is one of them
+    pub(crate) tablebase: Option<Arc<Tablebase>>,
+    /// set from another thread to stop a search, like the caller's progress does
+    pub(crate) stop: Option<Arc<AtomicBool>>,
+    rng: Rng,
+}
+
+/// what a search may still spend, checked at every position it looks at
+struct Budget<'a>{
+    deadline: Option<Instant>,
+    stop: Option<&'a AtomicBool>,
+    /// positions evaluated so far
+    nodes: usize,
+}
+
+impl Budget<'_>{
+    fn spent(&self)->bool{
+        self.deadline.is_some_and(|deadline|Instant::now() >= deadline)
+            || self.stop.is_some_and(|stop|stop.load(Ordering::Relaxed))
+    }
+}
+
+impl Engine{
+    pub(crate) fn new(weights: Weights, noise: f32, seed: u64)->Self{
+        Engine { weights, noise, book_games: 3, tablebase: None, stop: None, rng: Rng::new(seed) }
+    }
+
+    /// how good the position after a turn is for `player`, whoever took it.
+    /// Evaluations stay far below `DECISIVE`, they go through `Board`.
+    fn judge(&self, before: &Position, after: &Position, player: u8)->f32{
+        if after.lost_home(player) {
+            return -DECISIVE;
+        }
+        let mut score = Board::from(*after).advantage(player, &self.weights);
+        for opponent in (0..before.player_count()).filter(|other|*other != player){
+            if before.has_homeworld(opponent) && after.lost_home(opponent) {
+                score += DECISIVE;
+            }
+        }
+        score
+    }
+
+    /// `judge` for a turn of `player`, looking out for replies that lose at once
+    fn score(&self, before: &Position, after: &Position, player: u8)->f32{
+        let score = self.judge(before, after, player);
+        // replies include catastrophes left standing
+        if score > -DECISIVE && after.refuted(player) {
+            return score - BLUNDER;
+        }
+        score
+    }
+
+    /// how good `after`, reached by a turn on `before`, is for `player` with
+    /// `plies` more turns played by everyone at their best, and those turns.
+    /// Runs on `Position`, so the replies' ship ids only say the kind. None
+    /// once `budget` is spent.
+    fn line(&self, before: &Position, after: &Position, player: u8, plies: u8, budget: &mut Budget)->Option<(f32, Vec<Vec<Action>>)>{
+        if budget.spent() {
+            return None;
+        }
+        budget.nodes += 1;
+        let score = self.judge(before, after, player);
+        let mut next = *after;
+        next.end_turn();
+        let mover = next.current_player();
+        // solved positions are worth what the table says, however many plies are left
+        if score.abs() < DECISIVE / 2.0 {
+            if let Some(value) = self.tablebase.as_ref().and_then(|tablebase|tablebase.probe(&Board::from(next))) {
+                let value = if mover == player { value } else { value.previous() };
+                return Some((value.score(), Vec::new()));
+            }
+        }
+        // someone won or lost, or the one to move is out or still to pick a homeworld
+        if plies == 0 || score.abs() >= DECISIVE / 2.0 || !next.has_homeworld(mover) {
+            return Some((score, Vec::new()));
+        }
+        let mut best: Option<(f32, Vec<Vec<Action>>)> = None;
+        for (turn, reply) in next.legal_turns(){
+            let (score, mut rest) = self.line(&next, &reply, player, plies - 1, budget)?;
+            let better = best.as_ref().is_none_or(|(best, _)|match mover == player{
+                true => score > *best,
+                false => score < *best,
+            });
+            if better {
+                rest.insert(0, turn);
+                best = Some((score, rest));
+            }
+        }
+        Some(best.unwrap_or((score, Vec::new())))
+    }
+
+    /// finds the turn to play for the player to move on `board`, scoring
+    /// turns until `limits` run out
+    pub(crate) fn search(&mut self, board: &Board, book: Option<&Book>, limits: Limits)->Search{
+        self.search_with(board, book, limits, |_|true)
+    }
+
+    /// `search` that shows `progress` the best turn so far every now and then,
+    /// and stops early once `progress` returns false. Every depth scores the
+    /// best turns of the one before first, so a depth cut short still counts.
+    pub(crate) fn search_with(&mut self, board: &Board, book: Option<&Book>, limits: Limits, mut progress: impl FnMut(&Search)->bool)->Search{
+        let start = Instant::now();
+        if let Some(turn) = book.and_then(|book|book.best(board, self.book_games)) {
+            return Search { book: true, ..Search::found(turn.to_vec(), 0.0) };
+        }
+        if let Some((turn, value)) = self.tablebase.as_ref().and_then(|tablebase|tablebase.best_turn(board)) {
+            return Search { solved: true, ..Search::found(turn, value.score()) };
+        }
+        let player = board.current_player();
+        if board.homeworld(player).is_none() {
+            let advice = board.homeworld_advice(player);
+            // best first, pick among those tied for first
+            let Some(top) = advice.first().map(|advice|advice.score) else {
+                return Search::found(Vec::new(), 0.0);
+            };
+            let tied: Vec<_> = advice.iter().take_while(|advice|advice.score == top).collect();
+            let choice = tied[self.rng.below(tied.len())];
+            let turn = vec![Action::CreateHomeworld { stars: choice.stars, ship: choice.ship }];
+            return Search { scored: advice.len(), total: advice.len(), depth: 1, ..Search::found(turn, top as f32) };
+        }
+
+        // the turns played are the board's, the search runs on positions.
+        // The noise stays with a turn at every depth.
+        let root = Position::from(board);
+        let mut turns: Vec<(Vec<Action>, Position, f32)> = board.legal_turns().into_iter()
+            .map(|(turn, after)|(turn, Position::from(&after), self.noise * self.rng.unit()))
+            .collect();
+        let total = turns.len();
+        let max_depth = limits.depth.unwrap_or(if limits.time.is_some() { MAX_DEPTH } else { 1 }).max(1);
+        let mut result = Search::found(Vec::new(), 0.0);
+        let mut scored_overall = 0;
+        let mut budget = Budget { deadline: limits.time.map(|time|start + time), stop: self.stop.as_deref(), nodes: 0 };
+        for depth in 1..=max_depth{
+            let mut best: Option<(f32, Vec<Vec<Action>>)> = None;
+            let mut scores = Vec::with_capacity(total);
+            let mut cut = false;
+            for (turn, after, noise) in &turns{
+                let out_of_time = limits.time.is_some_and(|time|start.elapsed() >= time);
+                let out_of_turns = limits.turns.is_some_and(|turns|scored_overall >= turns);
+                if (best.is_some() || depth > 1) && (out_of_time || out_of_turns) {
+                    cut = true;
+                    break;
+                }
+                let line = match depth{
+                    1 => {
+                        budget.nodes += 1;
+                        Some((self.score(&root, after, player), Vec::new()))
+                    },
+                    _ => self.line(&root, after, player, depth - 1, &mut budget),
+                };
+                let Some((score, rest)) = line else {
+                    cut = true;
+                    break;
+                };
+                let score = score + noise;
+                scores.push(score);
+                scored_overall += 1;
+                let improved = best.as_ref().is_none_or(|(best, _)|score > *best);
+                if improved {
+                    best = Some((score, std::iter::once(turn.clone()).chain(rest).collect()));
+                }
+                // cloning the best line every time would cost more than scoring it
+                if improved || scores.len() % 32 == 0 {
+                    let (score, pv) = best.clone().unwrap_or_default();
+                    result = Search { turn: pv[0].clone(), score, scored: scores.len(), total, depth, pv, nodes: budget.nodes, book: false, solved: false };
+                    if !progress(&result) {
+                        cut = true;
+                        break;
+                    }
+                }
+            }
+            if let Some((score, pv)) = best {
+                result = Search { turn: pv[0].clone(), score, scored: scores.len(), total, depth, pv, nodes: budget.nodes, book: false, solved: false };
+            }
+            if cut || scores.len() < total {
+                break;
+            }
+            // best first for the next depth
+            let mut ranked: Vec<_> = scores.into_iter().zip(turns).collect();
+            ranked.sort_by(|(a, _), (b, _)|b.total_cmp(a));
+            turns = ranked.into_iter().map(|(_, turn)|turn).collect();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn takes_a_win_on_the_spot(){
+        use {Color::*, Size::*};
+        // player 1's only ship at home is small, our large ship is next to it with red at hand
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![piece(Red, Large)], vec![piece(Blue, Small)]]),
+        ]);
+        let mut engine = Engine::new(Weights::default(), 0.5, 1);
+        let turn = engine.search(&board, None, Limits::default()).turn;
+        assert_eq!(turn, vec![Action::CaptureShip { ship: piece(Blue, Small), star: piece(Yellow, Small) }]);
+    }
+
+    #[test]
+    fn picks_a_homeworld(){
+        let board = Board::new(2);
+        let mut engine = Engine::new(Weights::default(), 0.0, 7);
+        let turn = engine.search(&board, None, Limits::default()).turn;
+        assert!(matches!(turn.as_slice(), [Action::CreateHomeworld { .. }]));
+        assert!(board.apply_action(turn[0]).is_some());
+    }
+
+    #[test]
+    fn limits_cut_the_search_short(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large), piece(Yellow, Medium)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        let full = engine.search(&board, None, Limits::default());
+        assert!(full.scored > 2);
+        let short = engine.search(&board, None, Limits { turns: Some(2), ..Limits::default() });
+        assert_eq!(short.scored, 2);
+        // the first turn is always scored
+        let rushed = engine.search(&board, None, Limits { time: Some(Duration::ZERO), ..Limits::default() });
+        assert_eq!(rushed.scored, 1);
+
+        // a caller that has seen enough stops it the same way
+        let mut reports = Vec::new();
+        let stopped = engine.search_with(&board, None, Limits::default(), |search|{
+            reports.push(search.scored);
+            false
+        });
+        assert_eq!(stopped.scored, 1);
+        assert_eq!(reports, vec![1]);
+        assert_eq!(stopped.total, full.total);
+    }
+
+    #[test]
+    fn deeper_searches_look_at_replies(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large), piece(Yellow, Medium)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        let shallow = engine.search(&board, None, Limits::default());
+        assert_eq!(shallow.depth, 1);
+        assert_eq!(shallow.pv, vec![shallow.turn.clone()]);
+        let deep = engine.search(&board, None, Limits { depth: Some(2), ..Limits::default() });
+        assert_eq!(deep.depth, 2);
+        assert_eq!(deep.pv.first(), Some(&deep.turn));
+        assert_eq!(deep.pv.len(), 2);
+        assert!(deep.nodes > shallow.nodes);
+    }
+
+    #[test]
+    fn lines_end_in_the_tablebase(){
+        let set: PieceSet = "rg/13/2".parse().unwrap();
+        let table = Arc::new(Tablebase::solve(set, 100_000).unwrap());
+        let mut board = Board::with_set(2, set);
+        for _ in 0..2{
+            board = board.legal_turns().swap_remove(0).1;
+            board.end_turn();
+        }
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        engine.tablebase = Some(table.clone());
+        let player = board.current_player();
+        let root = Position::from(&board);
+        let mut checked = 0;
+        for (_, after) in board.legal_turns(){
+            let position = Position::from(&after);
+            if engine.judge(&root, &position, player).abs() >= DECISIVE / 2.0 {
+                continue;
+            }
+            let mut next = after.clone();
+            next.end_turn();
+            let value = table.probe(&next).unwrap().previous();
+            let mut budget = Budget { deadline: None, stop: None, nodes: 0 };
+            assert_eq!(engine.line(&root, &position, player, 3, &mut budget), Some((value.score(), Vec::new())));
+            // the table answers without looking further
+            assert_eq!(budget.nodes, 1);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn deeper_lines_stop_in_time(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large), piece(Yellow, Medium)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        let shallow = engine.search(&board, None, Limits::default());
+
+        // told to stop, the search keeps the first depth, which looks at no replies
+        engine.stop = Some(Arc::new(AtomicBool::new(true)));
+        let stopped = engine.search(&board, None, Limits { depth: Some(3), ..Limits::default() });
+        assert_eq!((stopped.depth, stopped.scored, stopped.nodes), (1, shallow.total, shallow.nodes));
+        engine.stop = None;
+
+        let start = Instant::now();
+        let timed = engine.search(&board, None, Limits { time: Some(Duration::from_millis(20)), depth: Some(MAX_DEPTH), ..Limits::default() });
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(timed.depth < MAX_DEPTH);
+    }
+}
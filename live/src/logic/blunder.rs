@@ -0,0 +1,109 @@
+#[cfg(feature = "window")]
+use super::*;
+#[cfg(feature = "window")]
+use super::eval::Weights;
+#[cfg(feature = "window")]
+use super::narration::narrate_turn;
+use super::position::Position;
+
+#[cfg(feature = "window")]
+/// an opponent's reply that makes a player lose on the spot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Refutation{
+    pub(crate) opponent: u8,
+    /// the board the reply is taken on, with `opponent` to move
+    pub(crate) before: Board,
+    pub(crate) turn: Vec<Action>,
+}
+
+#[cfg(feature = "window")]
+impl Refutation{
+    /// the reply in words, players called by `names`
+    pub(crate) fn describe(&self, names: &[String])->String{
+        narrate_turn(&self.before, &self.turn, names)
+    }
+}
+
+/// has `player` lost their homeworld or every ship there
+#[cfg(feature = "window")]
+pub(super) fn lost_home(board: &Board, player: u8)->bool{
+    board.homeworld(player)
+        .and_then(|id|board.system(id))
+        .is_none_or(|home|home.ships[player as usize].is_empty())
+}
+
+#[cfg(feature = "window")]
+impl Board{
+    /// one-reply search for the position after `player`'s candidate turn:
+    /// every legal turn of every opponent, as if it were theirs next. Of the
+    /// replies that take the homeworld or every ship there, the one the
+    /// opponent evaluates best is returned.
+    pub(crate) fn blunder(&self, player: u8, weights: &Weights)->Option<Refutation>{
+        self.homeworld(player)?;
+        let mut best: Option<(f32, Refutation)> = None;
+        for opponent in (0..self.player_count).filter(|other|*other != player && self.homeworld(*other).is_some()){
+            let mut before = self.clone();
+            before.current_player = opponent;
+            for (turn, after) in before.legal_turns(){
+                if !lost_home(&after, player) {
+                    continue;
+                }
+                let score = after.advantage(opponent, weights);
+                if best.as_ref().is_none_or(|(best, _)|score > *best) {
+                    best = Some((score, Refutation { opponent, before: before.clone(), turn }));
+                }
+            }
+        }
+        best.map(|(_, refutation)|refutation)
+    }
+}
+
+impl Position{
+    /// `Board::blunder` for search, which only needs to know if there is a refutation
+    pub(super) fn refuted(&self, player: u8)->bool{
+        if !self.has_homeworld(player) {
+            return false;
+        }
+        (0..self.player_count())
+            .filter(|other|*other != player && self.has_homeworld(*other))
+            .any(|opponent|self.with_current_player(opponent).legal_turns().iter().any(|(_, after)|after.lost_home(player)))
+    }
+}
+
+#[cfg(all(test, feature = "window"))]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn lone_defender_next_to_a_bigger_attacker(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Medium)], vec![piece(Yellow, Large)]]),
+            system(vec![piece(Green, Small), piece(Yellow, Medium)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let weights = Weights::default();
+        let refutation = board.blunder(0, &weights).unwrap();
+        assert_eq!(refutation.opponent, 1);
+        assert_eq!(refutation.turn, vec![Action::CaptureShip { ship: piece(Green, Medium), star: piece(Red, Small) }]);
+        assert_eq!(board.blunder(1, &weights), None);
+    }
+
+    #[test]
+    fn fourth_piece_at_home(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Blue, Medium), piece(Blue, Large)], Some(0), [vec![Id::new(Blue, Small, 1)], vec![]]),
+            system(vec![piece(Yellow, Small)], Some(1), [vec![], vec![piece(Blue, Small)]]),
+        ]);
+        let refutation = board.blunder(0, &Weights::default()).unwrap();
+        assert_eq!(refutation.turn, vec![
+            Action::MoveShip { ship: piece(Blue, Small), start_star: piece(Yellow, Small), end_star: piece(Blue, Medium) },
+            Action::Catastrophe { star: piece(Blue, Medium), color: Blue },
+        ]);
+        assert_eq!(
+            refutation.describe(&[]),
+            "player 1 moves their small blue from home to player 0's home, then sets off a blue catastrophe at player 0's home",
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use super::*;
+use super::position::Position;
+use super::record::Record;
+use super::game::Game;
+
+/// one turn played from a book position and how those games went for the
+/// player who played it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Continuation{
+    pub(crate) turn: Vec<Action>,
+    pub(crate) games: u32,
+    pub(crate) wins: u32,
+    pub(crate) draws: u32,
+}
+
+impl Continuation{
+    /// wins plus half the draws, per game
+    pub(crate) fn win_rate(&self)->f32{
+        (self.wins as f32 + self.draws as f32 / 2.0) / self.games as f32
+    }
+}
+
+/// the turns played from positions seen in finished games
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Book{
+    positions: HashMap<Position, Vec<Continuation>>,
+}
+
+impl Book{
+    /// adds every turn of a finished game, none if it is unfinished or doesn't replay
+    pub(crate) fn add(&mut self, record: &Record)->Option<()>{
+        let result = record.result?;
+        // replay first so a broken record adds nothing
+        let game = Game::replay(record, None)?;
+        for (position, turn) in game.played(){
+            let player = position.current_player();
+            let continuations = self.positions.entry(*position).or_default();
+            let i = match continuations.iter().position(|continuation|continuation.turn == *turn){
+                Some(i) => i,
+                None => {
+                    continuations.push(Continuation { turn: turn.clone(), games: 0, wins: 0, draws: 0 });
+                    continuations.len() - 1
+                },
+            };
+            let continuation = &mut continuations[i];
+            continuation.games += 1;
+            match result{
+                Some(winner) if winner == player => continuation.wins += 1,
+                None => continuation.draws += 1,
+                _ => {},
+            }
+        }
+        Some(())
+    }
+
+    /// compiles every file in `dir`, returns how many of them couldn't be read
+    /// or weren't finished games. An error only if `dir` can't be listed.
+    pub(crate) fn from_dir(dir: impl AsRef<std::path::Path>)->std::io::Result<(Book, usize)>{
+        let mut book = Book::default();
+        let mut skipped = 0;
+        for entry in std::fs::read_dir(dir)?{
+            let text = entry.and_then(|entry|std::fs::read_to_string(entry.path()));
+            let added = text.ok().and_then(|text|Record::parse(&text)).and_then(|record|book.add(&record));
+            if added.is_none() {
+                skipped += 1;
+            }
+        }
+        Ok((book, skipped))
+    }
+
+    /// what was played from here, best win rate first
+    pub(crate) fn continuations(&self, board: &Board)->Vec<&Continuation>{
+        let mut continuations: Vec<&Continuation> = self.positions
+            .get(&Position::from(board))
+            .map(|continuations|continuations.iter().collect())
+            .unwrap_or_default();
+        continuations.sort_by(|a, b|b.win_rate().total_cmp(&a.win_rate()).then(b.games.cmp(&a.games)));
+        continuations
+    }
+
+    /// the best known turn that was played at least `min_games` times and
+    /// can be played on `board`, for computer players
+    pub(crate) fn best(&self, board: &Board, min_games: u32)->Option<&[Action]>{
+        self.continuations(board).into_iter()
+            .filter(|continuation|continuation.games >= min_games)
+            .find(|continuation|{
+                continuation.turn.iter().try_fold(board.clone(), |board, action|board.apply_action(*action)).is_some()
+            })
+            .map(|continuation|continuation.turn.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::piece;
+
+    fn opening(stars: [Id; 2], ship: Id, reply: [Id; 3], result: Option<u8>)->Record{
+        Record {
+            player_count: 2,
+            set: PieceSet::STANDARD,
+            result: Some(result),
+            turns: vec![
+                vec![Action::CreateHomeworld { stars, ship }],
+                vec![Action::CreateHomeworld { stars: [reply[0], reply[1]], ship: reply[2] }],
+            ],
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn win_rates_per_continuation(){
+        use {Color::*, Size::*};
+        let good = [piece(Red, Small), piece(Blue, Medium)];
+        let greens = [piece(Green, Medium), Id::new(Green, Medium, 1)];
+        let reply = [piece(Red, Large), piece(Blue, Small), piece(Yellow, Medium)];
+
+        let mut book = Book::default();
+        book.add(&opening(good, piece(Green, Large), reply, Some(0))).unwrap();
+        book.add(&opening(good, piece(Green, Large), reply, None)).unwrap();
+        book.add(&opening(greens, piece(Yellow, Large), reply, Some(1))).unwrap();
+        // unfinished games and broken records are left out
+        let mut unfinished = opening(good, piece(Green, Large), reply, None);
+        unfinished.result = None;
+        assert!(book.add(&unfinished).is_none());
+        assert!(book.add(&opening(good, piece(Green, Large), [reply[0], reply[0], reply[2]], Some(0))).is_none());
+
+        let start = Board::new(2);
+        let continuations = book.continuations(&start);
+        assert_eq!(continuations.len(), 2);
+        assert_eq!((continuations[0].games, continuations[0].wins, continuations[0].draws), (2, 1, 1));
+        assert_eq!(continuations[0].win_rate(), 0.75);
+        assert_eq!(continuations[1].win_rate(), 0.0);
+        assert_eq!(book.best(&start, 1), Some(continuations[0].turn.as_slice()));
+        assert_eq!(book.best(&start, 3), None);
+
+        // the reply is booked from the second player's point of view
+        let board = start.apply_action(continuations[0].turn[0]).map(|mut board|{
+            board.end_turn();
+            board
+        }).unwrap();
+        let replies = book.continuations(&board);
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].win_rate(), 0.25);
+    }
+
+    #[test]
+    fn games_replay_with_their_decisions(){
+        let play = |game: &mut Game|{
+            let (turn, _) = game.board().legal_turns().swap_remove(0);
+            for action in turn{
+                game.apply_action(action).unwrap();
+            }
+            game.end_turn();
+        };
+        let mut game = Game::new(3, None);
+        for _ in 0..3{
+            play(&mut game);
+        }
+        // the first player resigns on their turn, the others play on
+        game.decide(0, Decision::Resign).unwrap();
+        let second = game.board().clone();
+        play(&mut game);
+        let third = game.board().clone();
+        play(&mut game);
+        game.decide(1, Decision::Resign).unwrap();
+        let record = game.record();
+        assert_eq!(record.result, Some(Some(2)));
+
+        let mut book = Book::default();
+        book.add(&record).unwrap();
+        let continuations = book.continuations(&second);
+        assert_eq!((continuations.len(), continuations[0].wins), (1, 0));
+        let continuations = book.continuations(&third);
+        assert_eq!((continuations.len(), continuations[0].wins), (1, 1));
+    }
+
+    #[test]
+    fn unreadable_files_are_skipped(){
+        let dir = std::env::temp_dir().join(format!("book-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let record = opening([piece(Color::Red, Size::Small), piece(Color::Blue, Size::Medium)], piece(Color::Green, Size::Large),
+            [piece(Color::Red, Size::Large), piece(Color::Blue, Size::Small), piece(Color::Yellow, Size::Medium)], Some(0));
+        std::fs::write(dir.join("game.txt"), record.to_string()).unwrap();
+        std::fs::write(dir.join("binary.txt"), [0xff, 0xfe, 0x00]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a game").unwrap();
+        let (book, skipped) = Book::from_dir(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(skipped, 2);
+        assert_eq!(book.continuations(&Board::new(2)).len(), 1);
+        assert!(Book::from_dir(&dir).is_err());
+    }
+}
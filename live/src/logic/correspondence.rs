@@ -0,0 +1,211 @@
+use super::*;
+use super::game::Game;
+use super::record::{format_turn, parse_turn};
+use super::snapshot::{fnv1a, FNV_START};
+
+/// one step of a correspondence game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Entry{
+    Turn(Vec<Action>),
+    Decision(u8, Decision),
+}
+
+/// `turn build g1a r1a` or `decision 1 offer`
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Entry::Turn(turn) => write!(f, "turn {}", format_turn(turn)),
+            Entry::Decision(player, decision) => write!(f, "decision {player} {decision}"),
+        }
+    }
+}
+
+fn parse_entry(line: &str)->Option<Entry>{
+    let (kind, rest) = line.split_once(' ')?;
+    match kind{
+        "turn" => Some(Entry::Turn(parse_turn(rest)?)),
+        "decision" => {
+            let (player, decision) = rest.trim().split_once(' ')?;
+            Some(Entry::Decision(player.parse().ok()?, decision.parse().ok()?))
+        },
+        _ => None,
+    }
+}
+
+/// a game played by passing a file around, one turn at a time. Every entry
+/// is followed by a hash of the hash before it, the entry and the board it
+/// led to, so an earlier turn changed by hand breaks the chain from there on.
+/// Entries cut off the end can't be told from the file alone, the players
+/// compare `head` for that.
+#[derive(Debug, Clone)]
+pub(crate) struct Correspondence{
+    game: Game,
+    no_progress_limit: Option<u32>,
+    entries: Vec<Entry>,
+    /// the chain, one hash per entry
+    hashes: Vec<u64>,
+}
+
+impl Correspondence{
+    pub(crate) fn new(player_count: u8, no_progress_limit: Option<u32>)->Self{
+        Correspondence {
+            game: Game::new(player_count, no_progress_limit),
+            no_progress_limit,
+            entries: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn game(&self)->&Game{
+        &self.game
+    }
+
+    /// the first line of the file, the chain starts from its hash
+    fn header(&self)->String{
+        let limit = self.no_progress_limit.map_or("none".to_string(), |limit|limit.to_string());
+        format!("players {} limit {limit}", self.game.board().player_count())
+    }
+
+    /// the last hash of the chain, it changes with every entry
+    pub(crate) fn head(&self)->u64{
+        self.hashes.last().copied().unwrap_or_else(||fnv1a(FNV_START, self.header().as_bytes()))
+    }
+
+    /// adds `entry`, which was just applied to the game, to the chain
+    fn push(&mut self, entry: Entry){
+        let link = format!("{entry}\n{:016x}", self.game.board().checksum());
+        let hash = fnv1a(self.head(), link.as_bytes());
+        self.entries.push(entry);
+        self.hashes.push(hash);
+    }
+
+    /// plays a whole turn for the player to move and ends it,
+    /// none if it can't be played
+    pub(crate) fn play(&mut self, turn: &[Action])->Option<()>{
+        if self.game.outcome().is_some() {
+            return None;
+        }
+        // check the whole turn first so a bad one leaves no trace
+        self.game.board().legal_turn(turn)?;
+        for action in turn{
+            self.game.apply_action(*action)?;
+        }
+        self.game.end_turn();
+        self.push(Entry::Turn(turn.to_vec()));
+        Some(())
+    }
+
+    pub(crate) fn decide(&mut self, player: u8, decision: Decision)->Option<()>{
+        self.game.decide(player, decision)?;
+        self.push(Entry::Decision(player, decision));
+        Some(())
+    }
+
+    /// reads a saved game, checking every turn and every hash
+    pub(crate) fn parse(text: &str)->Result<Self, String>{
+        let mut lines = text.lines().enumerate().map(|(i, line)|(i + 1, line.trim())).filter(|(_, line)|!line.is_empty());
+        let (_, header) = lines.next().ok_or("the file is empty")?;
+        let words: Vec<&str> = header.split_whitespace().collect();
+        let (player_count, limit) = match words.as_slice(){
+            ["players", player_count, "limit", limit] => (*player_count, *limit),
+            _ => return Err(format!("line 1: expected `players N limit L`, found `{header}`")),
+        };
+        let player_count = player_count.parse().ok().filter(|count|(2..=4).contains(count))
+            .ok_or(format!("line 1: can't have {player_count} players"))?;
+        let no_progress_limit = match limit{
+            "none" => None,
+            limit => Some(limit.parse().map_err(|_|format!("line 1: bad limit {limit}"))?),
+        };
+
+        let mut game = Correspondence::new(player_count, no_progress_limit);
+        while let Some((number, line)) = lines.next(){
+            let entry = parse_entry(line).ok_or(format!("line {number}: can't read `{line}`"))?;
+            let applied = match &entry{
+                Entry::Turn(turn) => game.play(turn),
+                Entry::Decision(player, decision) => game.decide(*player, *decision),
+            };
+            applied.ok_or(format!("line {number}: `{line}` can't be played here"))?;
+            let hash = lines.next()
+                .and_then(|(_, line)|line.strip_prefix("hash "))
+                .and_then(|hash|u64::from_str_radix(hash.trim(), 16).ok())
+                .ok_or(format!("line {number}: no hash after `{line}`"))?;
+            if hash != game.head() {
+                return Err(format!("line {number}: the hash doesn't match, `{line}` or something before it was changed"));
+            }
+        }
+        Ok(game)
+    }
+}
+
+impl std::fmt::Display for Correspondence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.header())?;
+        for (entry, hash) in self.entries.iter().zip(&self.hashes){
+            writeln!(f, "{entry}")?;
+            writeln!(f, "hash {hash:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::piece;
+
+    fn opening()->Correspondence{
+        use {Color::*, Size::*};
+        let mut game = Correspondence::new(2, Some(50));
+        game.play(&[Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) }]).unwrap();
+        game.play(&[Action::CreateHomeworld { stars: [piece(Yellow, Small), piece(Blue, Large)], ship: piece(Green, Medium) }]).unwrap();
+        game.decide(0, Decision::OfferDraw).unwrap();
+        game
+    }
+
+    #[test]
+    fn saved_games_reopen(){
+        let game = opening();
+        let text = game.to_string();
+        assert!(text.starts_with("players 2 limit 50\nturn homeworld r1a b2a g3a\nhash "));
+        let reopened = Correspondence::parse(&text).unwrap();
+        assert_eq!(reopened.game().board(), game.game().board());
+        assert_eq!(reopened.game().draw_offer(), Some(0));
+        assert_eq!(reopened.head(), game.head());
+        assert_eq!(reopened.to_string(), text);
+
+        // illegal turns are refused and leave nothing behind
+        let mut more = reopened.clone();
+        let second_home = Action::CreateHomeworld { stars: [piece(Color::Red, Size::Medium), piece(Color::Red, Size::Large)], ship: piece(Color::Yellow, Size::Large) };
+        assert!(more.play(&[second_home]).is_none());
+        // each build is fine, two without a sacrifice aren't
+        let builds = [
+            Action::CreateShip { ship: piece(Color::Green, Size::Small), star: piece(Color::Red, Size::Small) },
+            Action::CreateShip { ship: Id::new(Color::Green, Size::Small, 1), star: piece(Color::Red, Size::Small) },
+        ];
+        assert!(more.play(&builds).is_none());
+        assert_eq!(more.to_string(), text);
+    }
+
+    #[test]
+    fn changes_break_the_chain(){
+        let text = opening().to_string();
+
+        // a different opening with the hashes left as they were
+        let changed = text.replacen("homeworld r1a b2a g3a", "homeworld r1a b2a y3a", 1);
+        let error = Correspondence::parse(&changed).unwrap_err();
+        assert!(error.starts_with("line 2: the hash doesn't match"), "{error}");
+
+        // a hash edited by hand
+        let lines: Vec<&str> = text.lines().collect();
+        let mut edited = lines.clone();
+        edited[4] = "hash 0000000000000000";
+        let error = Correspondence::parse(&edited.join("\n")).unwrap_err();
+        assert!(error.starts_with("line 4:"), "{error}");
+
+        // a turn that can't be played, and one without its hash
+        assert!(Correspondence::parse(&format!("{text}turn homeworld r1a b2a g3a\nhash 00\n")).unwrap_err().contains("can't be played"));
+        assert!(Correspondence::parse(&format!("{text}turn build g1a r1a; build g1b r1a\nhash 00\n")).unwrap_err().contains("can't be played"));
+        assert!(Correspondence::parse(&lines[..4].join("\n")).unwrap_err().contains("no hash"));
+        assert!(Correspondence::parse("players 9 limit 50").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use super::*;
+
+/// what an evaluation looks at, each one counted for a single player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Feature{
+    /// pips of all ships, small is 1 and large is 3
+    Fleet,
+    /// pips at home, minus enemy pips there and enemy ships in connected systems
+    HomeDefence,
+    /// colors the player can use somewhere
+    Economy,
+    /// large ships, the ones nothing bigger can capture
+    LargePieces,
+    /// colors one piece from a catastrophe where the player has something to lose
+    CatastropheExposure,
+    /// systems the player's ships can get to within two moves
+    Mobility,
+}
+
+pub(crate) const FEATURES: [Feature; 6] = [
+    Feature::Fleet,
+    Feature::HomeDefence,
+    Feature::Economy,
+    Feature::LargePieces,
+    Feature::CatastropheExposure,
+    Feature::Mobility,
+];
+
+impl Feature{
+    /// the key in the weights file
+    pub(crate) fn name(self)->&'static str{
+        match self{
+            Feature::Fleet => "fleet",
+            Feature::HomeDefence => "home_defence",
+            Feature::Economy => "economy",
+            Feature::LargePieces => "large_pieces",
+            Feature::CatastropheExposure => "catastrophe_exposure",
+            Feature::Mobility => "mobility",
+        }
+    }
+}
+
+/// how much every feature counts, indexed like `FEATURES`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Weights([f32; FEATURES.len()]);
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights([1.0, 2.0, 1.5, 1.0, -3.0, 0.5])
+    }
+}
+
+impl Weights{
+    pub(crate) fn get(&self, feature: Feature)->f32{
+        self.0[feature as usize]
+    }
+
+    /// `name = value` lines, `#` starts a comment. Features that aren't
+    /// listed keep their default weight.
+    pub(crate) fn parse(text: &str)->Result<Self, String>{
+        let mut weights = Weights::default();
+        for (i, line) in text.lines().enumerate(){
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str|format!("line {}: {message}", i + 1);
+            let (name, value) = line.split_once('=').ok_or_else(||error("expected `name = value`"))?;
+            let feature = FEATURES.iter()
+                .find(|feature|feature.name() == name.trim())
+                .ok_or_else(||error(&format!("unknown feature `{}`", name.trim())))?;
+            weights.0[*feature as usize] = value.trim().parse().map_err(|_|error("expected a number"))?;
+        }
+        Ok(weights)
+    }
+}
+
+/// a player's score and what it is made of
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Evaluation{
+    /// raw feature values, indexed like `FEATURES`
+    pub(crate) features: [i32; FEATURES.len()],
+    pub(crate) score: f32,
+}
+
+#[cfg(any(test, feature = "window"))]
+impl Evaluation{
+    pub(crate) fn get(&self, feature: Feature)->i32{
+        self.features[feature as usize]
+    }
+    /// every feature with its value and what it added to the score
+    pub(crate) fn breakdown<'a>(&'a self, weights: &'a Weights)->impl Iterator<Item = (Feature, i32, f32)> + 'a{
+        FEATURES.into_iter().map(|feature|(feature, self.get(feature), self.get(feature) as f32 * weights.get(feature)))
+    }
+}
+
+fn pips(ships: &[Id])->i32{
+    ships.iter().map(|ship|ship.size() as i32 + 1).sum()
+}
+
+impl Board{
+    /// static evaluation of the position for `player`, higher is better
+    pub(crate) fn evaluate(&self, player: u8, weights: &Weights)->Evaluation{
+        let mut features = [0; FEATURES.len()];
+
+        for (_, system) in self.systems(){
+            let ours = &system.ships[player as usize];
+            features[Feature::Fleet as usize] += pips(ours);
+            features[Feature::LargePieces as usize] += ours.iter().filter(|ship|ship.size() >= Size::Large).count() as i32;
+        }
+        let opponents = (0..self.player_count).filter(|other|*other != player);
+        if let Some(home) = self.homeworld(player).and_then(|id|self.system(id)) {
+            let enemies: i32 = opponents.clone().map(|other|pips(&home.ships[other as usize])).sum();
+            // counted from the board rather than `threats`, which is too slow for every evaluation
+            let nearby = self.neighbours(self.homeworld(player).expect("found above")).into_iter()
+                .filter_map(|id|self.system(id))
+                .map(|system|opponents.clone().map(|other|system.ships[other as usize].len() as i32).sum::<i32>())
+                .sum::<i32>();
+            features[Feature::HomeDefence as usize] = pips(&home.ships[player as usize]) - enemies - nearby;
+        }
+        features[Feature::Economy as usize] = self.power_summary()[player as usize].iter().count() as i32;
+        features[Feature::CatastropheExposure as usize] = self.systems()
+            .filter(|(_, system)|!system.ships[player as usize].is_empty() || system.home == Some(player))
+            .map(|(_, system)|COLORS.into_iter().filter(|color|system.color_count(*color) == 3).count() as i32)
+            .sum();
+        let occupied: Vec<SystemId> = self.systems()
+            .filter(|(_, system)|!system.ships[player as usize].is_empty())
+            .map(|(id, _)|id)
+            .collect();
+        features[Feature::Mobility as usize] = self.systems()
+            .filter(|(to, _)|!occupied.contains(to))
+            .filter(|(to, _)|occupied.iter().any(|from|self.distance(*from, *to).is_some_and(|steps|steps <= 2)))
+            .count() as i32;
+
+        let score = FEATURES.iter().map(|feature|features[*feature as usize] as f32 * weights.get(*feature)).sum();
+        Evaluation { features, score }
+    }
+
+    /// `player`'s score minus the best other score, positive when ahead
+    pub(crate) fn advantage(&self, player: u8, weights: &Weights)->f32{
+        let ours = self.evaluate(player, weights).score;
+        let best_other = (0..self.player_count)
+            .filter(|other|*other != player)
+            .map(|other|self.evaluate(other, weights).score)
+            .fold(f32::NEG_INFINITY, f32::max);
+        if best_other.is_finite() { ours - best_other } else { ours }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, two_homes};
+
+    #[test]
+    fn features_of_a_small_position(){
+        use {Color::*, Size::*};
+        let board = two_homes();
+        let weights = Weights::default();
+        let eval = board.evaluate(0, &weights);
+        assert_eq!(eval.get(Feature::Fleet), 5);
+        assert_eq!(eval.get(Feature::HomeDefence), 5);
+        assert_eq!(eval.get(Feature::Economy), 4);
+        assert_eq!(eval.get(Feature::LargePieces), 1);
+        assert_eq!(eval.get(Feature::CatastropheExposure), 0);
+        // the other homeworld, over a large star from the bank
+        assert_eq!(eval.get(Feature::Mobility), 1);
+        let total: f32 = eval.breakdown(&weights).map(|(_, _, score)|score).sum();
+        assert_eq!(total, eval.score);
+        // the two sides mirror each other
+        assert_eq!(board.advantage(0, &weights), 0.0);
+
+        let mut ahead = board.clone();
+        ahead.current_player = 1;
+        let ahead = ahead.apply_action(Action::DestroyShip { ship: piece(Red, Medium), star: piece(Yellow, Small) }).unwrap();
+        assert!(ahead.advantage(0, &weights) > 0.0);
+    }
+
+    #[test]
+    fn weights_file(){
+        let weights = Weights::parse("# try a defensive style\nhome_defence = 4\n\ncatastrophe_exposure=-1.5 # careful\n").unwrap();
+        assert_eq!(weights.get(Feature::HomeDefence), 4.0);
+        assert_eq!(weights.get(Feature::CatastropheExposure), -1.5);
+        assert_eq!(weights.get(Feature::Fleet), Weights::default().get(Feature::Fleet));
+
+        assert_eq!(Weights::parse("fleet 2"), Err("line 1: expected `name = value`".to_string()));
+        assert_eq!(Weights::parse("\nspeed = 2"), Err("line 2: unknown feature `speed`".to_string()));
+        assert_eq!(Weights::parse("fleet = lots"), Err("line 1: expected a number".to_string()));
+    }
+}
@@ -0,0 +1,428 @@
+use super::*;
+use super::position::Position;
+use super::record::Record;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason{
+    /// the same position with the same player to move came up three times
+    Repetition,
+    /// nothing entered or left play and no ship changed hands for too long
+    NoProgress,
+    /// everyone else resigned
+    Resignation,
+    /// every player still in the game accepted a draw offer
+    Agreement,
+    /// everyone else ran out of time
+    #[cfg(feature = "window")]
+    Timeout,
+    /// everyone else lost their homeworld or all ships at home
+    Elimination,
+}
+
+/// turn actions that don't touch the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision{
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+}
+
+/// a decision in the game record, `turn` indexes the position it was made in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Event{
+    pub(crate) turn: usize,
+    pub(crate) player: u8,
+    pub(crate) decision: Decision,
+}
+
+/// how a game ended, `winner` is none for a draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Outcome{
+    pub(crate) winner: Option<u8>,
+    pub(crate) reason: Reason,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.winner{
+            Some(player) => write!(f, "player {player} wins")?,
+            None => write!(f, "draw")?,
+        }
+        match self.reason{
+            Reason::Repetition => write!(f, " by threefold repetition"),
+            Reason::NoProgress => write!(f, ", no progress for too long"),
+            Reason::Resignation => write!(f, " by resignation"),
+            Reason::Agreement => write!(f, " by agreement"),
+            #[cfg(feature = "window")]
+            Reason::Timeout => write!(f, " on time"),
+            Reason::Elimination => write!(f, " by elimination"),
+        }
+    }
+}
+
+/// a board with what happened on it so far
+#[derive(Debug, Clone)]
+pub(crate) struct Game{
+    board: Board,
+    /// the position at the start of every turn, the current one last
+    history: Vec<Position>,
+    /// the actions of every finished turn
+    turns: Vec<Vec<Action>>,
+    /// the actions of the turn in progress
+    current_turn: Vec<Action>,
+    /// turns in a row without progress
+    quiet_turns: u32,
+    /// `material` when the current turn started
+    turn_start: (usize, Vec<usize>),
+    /// draw after this many quiet turns, none to play on forever
+    no_progress_limit: Option<u32>,
+    /// decisions in the order they were made
+    events: Vec<Event>,
+    /// players who resigned, ran out of time or were eliminated
+    out: Vec<bool>,
+    /// players who had a homeworld at some point and can lose it
+    had_home: Vec<bool>,
+    /// players who agreed to the draw on offer, the first one offered it
+    draw_offer: Vec<u8>,
+    outcome: Option<Outcome>,
+}
+
+/// what `Game` counts as progress: bank size and ships per player
+fn material(board: &Board)->(usize, Vec<usize>){
+    let mut ships = vec![0; board.player_count as usize];
+    for (_, system) in board.systems(){
+        for (player, player_ships) in system.ships.iter().enumerate(){
+            ships[player] += player_ships.len();
+        }
+    }
+    (board.bank.len(), ships)
+}
+
+impl Game{
+    /// at most 4 players, as `Position` has room for
+    pub(crate) fn new(player_count: u8, no_progress_limit: Option<u32>)->Self{
+        Self::from_board(Board::new(player_count), no_progress_limit)
+    }
+    pub(crate) fn from_board(board: Board, no_progress_limit: Option<u32>)->Self{
+        Game {
+            history: vec![Position::from(&board)],
+            turns: Vec::new(),
+            current_turn: Vec::new(),
+            turn_start: material(&board),
+            out: vec![false; board.player_count as usize],
+            had_home: (0..board.player_count).map(|player|board.homeworld(player).is_some()).collect(),
+            board,
+            quiet_turns: 0,
+            no_progress_limit,
+            events: Vec::new(),
+            draw_offer: Vec::new(),
+            outcome: None,
+        }
+    }
+    pub(crate) fn board(&self)->&Board{
+        &self.board
+    }
+    pub(crate) fn outcome(&self)->Option<Outcome>{
+        self.outcome
+    }
+    /// the game up to the last finished turn
+    pub(crate) fn record(&self)->Record{
+        Record {
+            player_count: self.board.player_count,
+            set: self.board.set,
+            result: self.outcome.map(|outcome|outcome.winner),
+            turns: self.turns.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// plays `record` through from an empty board, none if an action or a
+    /// decision is rejected. A result on time isn't replayed, timeouts aren't recorded.
+    pub(crate) fn replay(record: &Record, no_progress_limit: Option<u32>)->Option<Game>{
+        let mut game = Game::from_board(Board::with_set(record.player_count, record.set), no_progress_limit);
+        let mut events = record.events.iter().peekable();
+        for i in 0..=record.turns.len(){
+            while let Some(event) = events.next_if(|event|event.turn == i) {
+                game.decide(event.player, event.decision)?;
+            }
+            let Some(turn) = record.turns.get(i) else {
+                break;
+            };
+            for action in turn{
+                game.apply_action(*action)?;
+            }
+            game.end_turn();
+        }
+        // events out of order or after the last turn
+        match events.next(){
+            Some(_) => None,
+            None => Some(game),
+        }
+    }
+
+    /// every finished turn with the position it was played on
+    pub(super) fn played(&self)->impl Iterator<Item = (&Position, &Vec<Action>)>{
+        self.history.iter().zip(&self.turns)
+    }
+
+    /// the player who offered the draw still on the table
+    pub(crate) fn draw_offer(&self)->Option<u8>{
+        self.draw_offer.first().copied()
+    }
+    #[cfg(feature = "window")]
+    pub(crate) fn is_out(&self, player: u8)->bool{
+        self.out[player as usize]
+    }
+
+    fn players_left(&self)->impl Iterator<Item = u8> + '_{
+        (0..self.board.player_count).filter(|player|!self.out[*player as usize])
+    }
+
+    /// takes `players` out of the game, it ends for `reason` once at most one is left
+    fn knock_out(&mut self, players: &[u8], reason: Reason){
+        for player in players{
+            self.out[*player as usize] = true;
+        }
+        self.draw_offer.clear();
+        let left: Vec<u8> = self.players_left().collect();
+        if left.len() <= 1 {
+            self.outcome = Some(Outcome { winner: left.first().copied(), reason });
+        }
+    }
+
+    /// hands the turn to the next player still in the game
+    fn next_player(&mut self){
+        self.board.end_turn();
+        while self.out[self.board.current_player as usize] {
+            self.board.end_turn();
+        }
+    }
+
+    /// a decision by `player`, who doesn't have to be the one to move.
+    /// Rejects answering a draw nobody offered, offering a second one and
+    /// agreeing to one's own offer.
+    pub(crate) fn decide(&mut self, player: u8, decision: Decision)->Option<()>{
+        if self.outcome.is_some() || *self.out.get(player as usize)? {
+            return None;
+        }
+        match decision{
+            Decision::Resign => {
+                self.knock_out(&[player], Reason::Resignation);
+                if self.outcome.is_none() && player == self.board.current_player {
+                    self.next_player();
+                    // the turn starts over with the next player
+                    if let Some(start) = self.history.last_mut() {
+                        *start = Position::from(&self.board);
+                    }
+                }
+            },
+            Decision::OfferDraw => {
+                if !self.draw_offer.is_empty() {
+                    return None;
+                }
+                self.draw_offer.push(player);
+            },
+            Decision::AcceptDraw => {
+                if self.draw_offer.is_empty() || self.draw_offer.contains(&player) {
+                    return None;
+                }
+                self.draw_offer.push(player);
+                if self.players_left().all(|player|self.draw_offer.contains(&player)) {
+                    self.outcome = Some(Outcome { winner: None, reason: Reason::Agreement });
+                }
+            },
+            Decision::DeclineDraw => {
+                if self.draw_offer.is_empty() || self.draw_offer.contains(&player) {
+                    return None;
+                }
+                self.draw_offer.clear();
+            },
+        }
+        self.events.push(Event { turn: self.history.len() - 1, player, decision });
+        Some(())
+    }
+
+    /// `player`'s clock ran out
+    #[cfg(feature = "window")]
+    pub(crate) fn time_out(&mut self, player: u8){
+        if self.outcome.is_none() && !self.out[player as usize] {
+            self.knock_out(&[player], Reason::Timeout);
+            if self.outcome.is_none() && player == self.board.current_player {
+                self.next_player();
+            }
+        }
+    }
+
+    /// applies `action` for the player to move, leaves the game as it was when it is rejected
+    pub(crate) fn apply_action(&mut self, action: Action)->Option<()>{
+        if self.outcome.is_some() {
+            return None;
+        }
+        self.board = self.board.clone().apply_action(action)?;
+        self.current_turn.push(action);
+        Some(())
+    }
+
+    /// hands the turn on and checks for a draw
+    pub(crate) fn end_turn(&mut self){
+        if self.outcome.is_some() {
+            return;
+        }
+        self.turns.push(std::mem::take(&mut self.current_turn));
+        let material = material(&self.board);
+        self.quiet_turns = if material != self.turn_start { 0 } else { self.quiet_turns + 1 };
+        self.turn_start = material;
+
+        // playing on instead of answering turns a draw offer down
+        if self.draw_offer.first().is_some_and(|offerer|*offerer != self.board.current_player) {
+            self.draw_offer.clear();
+        }
+
+        for player in 0..self.board.player_count{
+            self.had_home[player as usize] |= self.board.homeworld(player).is_some();
+        }
+        let eliminated: Vec<u8> = self.players_left().filter(|player|{
+            let home = self.board.homeworld(*player).and_then(|id|self.board.system(id));
+            self.had_home[*player as usize] && home.is_none_or(|home|home.ships[*player as usize].is_empty())
+        }).collect();
+        if !eliminated.is_empty() {
+            self.knock_out(&eliminated, Reason::Elimination);
+            if self.outcome.is_some() {
+                return;
+            }
+        }
+
+        self.next_player();
+        let position = Position::from(&self.board);
+        self.history.push(position);
+
+        let repetitions = self.history.iter().filter(|earlier|**earlier == position).count();
+        if repetitions >= 3 {
+            self.outcome = Some(Outcome { winner: None, reason: Reason::Repetition });
+        }else if self.no_progress_limit.is_some_and(|limit|self.quiet_turns >= limit) {
+            self.outcome = Some(Outcome { winner: None, reason: Reason::NoProgress });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system, two_homes};
+
+    /// the player to move shuttles `ship` between the two homeworlds
+    fn shuttle(game: &mut Game, ship: Id, from: Id, to: Id){
+        game.apply_action(Action::MoveShip { ship, start_star: from, end_star: to }).unwrap();
+        game.end_turn();
+    }
+
+    #[test]
+    fn threefold_repetition_is_a_draw(){
+        use {Color::*, Size::*};
+        let mut game = Game::from_board(two_homes(), None);
+        let (home_0, home_1) = (piece(Red, Small), piece(Yellow, Small));
+        for cycle in 0..2{
+            shuttle(&mut game, piece(Green, Large), home_0, home_1);
+            shuttle(&mut game, piece(Blue, Large), home_1, home_0);
+            shuttle(&mut game, piece(Green, Large), home_1, home_0);
+            assert_eq!(game.outcome(), None, "cycle {cycle}");
+            shuttle(&mut game, piece(Blue, Large), home_0, home_1);
+        }
+        assert_eq!(game.outcome(), Some(Outcome { winner: None, reason: Reason::Repetition }));
+        assert!(game.apply_action(Action::MoveShip { ship: piece(Green, Large), start_star: home_0, end_star: home_1 }).is_none());
+    }
+
+    #[test]
+    fn quiet_turns_run_out(){
+        use {Color::*, Size::*};
+        let mut game = Game::from_board(two_homes(), Some(3));
+        let (home_0, home_1) = (piece(Red, Small), piece(Yellow, Small));
+        shuttle(&mut game, piece(Green, Large), home_0, home_1);
+        shuttle(&mut game, piece(Blue, Large), home_1, home_0);
+
+        // building a ship is progress
+        game.apply_action(Action::CreateShip { ship: piece(Green, Small), star: home_1 }).unwrap();
+        game.end_turn();
+        shuttle(&mut game, piece(Blue, Large), home_0, home_1);
+        shuttle(&mut game, piece(Green, Large), home_1, home_0);
+        assert_eq!(game.outcome(), None);
+        shuttle(&mut game, piece(Blue, Large), home_1, home_0);
+        assert_eq!(game.outcome(), Some(Outcome { winner: None, reason: Reason::NoProgress }));
+    }
+
+    #[test]
+    fn records_replay(){
+        use {Color::*, Size::*};
+        let mut game = Game::new(2, None);
+        game.apply_action(Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) }).unwrap();
+        game.end_turn();
+        game.apply_action(Action::CreateHomeworld { stars: [piece(Yellow, Large), piece(Blue, Small)], ship: piece(Green, Medium) }).unwrap();
+        game.end_turn();
+        game.end_turn();
+        // unfinished turns are not recorded
+        game.apply_action(Action::CreateShip { ship: piece(Green, Small), star: piece(Yellow, Large) }).unwrap();
+
+        let record = Record::parse(&game.record().to_string()).unwrap();
+        assert_eq!(record.turns.len(), 3);
+        assert_eq!(record.result, None);
+        let replayed = Game::replay(&record, None).unwrap();
+        assert_eq!(replayed.board().current_player(), 1);
+        assert_eq!(replayed.history, game.history);
+    }
+
+    #[test]
+    fn draw_offers_and_resignation(){
+        let mut game = Game::from_board(two_homes(), None);
+        assert!(game.decide(1, Decision::AcceptDraw).is_none());
+        game.decide(0, Decision::OfferDraw).unwrap();
+        assert!(game.decide(0, Decision::AcceptDraw).is_none());
+        game.decide(1, Decision::DeclineDraw).unwrap();
+        assert_eq!(game.draw_offer(), None);
+
+        // an offer nobody answers lapses once the opponent ends a turn
+        game.decide(0, Decision::OfferDraw).unwrap();
+        game.end_turn();
+        assert_eq!(game.draw_offer(), Some(0));
+        game.end_turn();
+        assert_eq!(game.draw_offer(), None);
+
+        game.decide(1, Decision::OfferDraw).unwrap();
+        game.decide(0, Decision::AcceptDraw).unwrap();
+        assert_eq!(game.outcome(), Some(Outcome { winner: None, reason: Reason::Agreement }));
+        assert!(game.decide(1, Decision::Resign).is_none());
+        let record = game.record();
+        assert_eq!(record.events.len(), 5);
+        assert_eq!(record.events[3], Event { turn: 2, player: 1, decision: Decision::OfferDraw });
+        // the decisions are saved and replayed with the turns
+        let replayed = Game::replay(&Record::parse(&record.to_string()).unwrap(), None).unwrap();
+        assert_eq!(replayed.outcome(), game.outcome());
+        assert_eq!(replayed.record(), record);
+
+        let mut game = Game::from_board(two_homes(), None);
+        game.decide(1, Decision::Resign).unwrap();
+        assert_eq!(game.outcome(), Some(Outcome { winner: Some(0), reason: Reason::Resignation }));
+    }
+
+    #[test]
+    fn losing_the_homeworld_eliminates(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Yellow, Large)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![piece(Green, Small)], vec![piece(Green, Large)]]),
+        ]);
+        let mut game = Game::from_board(board, None);
+        // a fourth green at player 1's homeworld takes their only ship with it
+        game.apply_action(Action::CreateShip { ship: Id::new(Green, Small, 1), star: piece(Yellow, Small) }).unwrap();
+        game.apply_action(Action::Catastrophe { star: piece(Yellow, Small), color: Green }).unwrap();
+        game.end_turn();
+        assert_eq!(game.outcome(), Some(Outcome { winner: Some(0), reason: Reason::Elimination }));
+
+        // nobody is eliminated before they had a homeworld
+        let mut game = Game::new(2, None);
+        game.apply_action(Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) }).unwrap();
+        game.end_turn();
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.board().current_player(), 1);
+    }
+}
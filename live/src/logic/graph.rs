@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use super::*;
+
+/// a place a ship can be: an existing system, or a star of some size
+/// that could still be discovered from the bank
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node{
+    System(SystemId),
+    Discovery(Size),
+}
+
+impl Board{
+    /// ships can move between `a` and `b` in one step
+    pub(crate) fn connected(&self, a: SystemId, b: SystemId)->bool{
+        match (self.system(a), self.system(b)){
+            (Some(a_system), Some(b_system)) => a != b && a_system.connected(b_system),
+            _ => false,
+        }
+    }
+
+    /// existing systems one move away from `system`
+    pub(crate) fn neighbours(&self, system: SystemId)->Vec<SystemId>{
+        self.systems()
+            .map(|(id, _)|id)
+            .filter(|id|self.connected(system, *id))
+            .collect()
+    }
+
+    fn node_sizes(&self, node: Node)->Vec<Size>{
+        match node{
+            Node::System(id) => self.system(id).map(|system|system.stars.iter().map(|star|star.size()).collect()).unwrap_or_default(),
+            Node::Discovery(size) => vec![size],
+        }
+    }
+
+    /// fewest moves for a ship to get from `from` to `to`, passing through
+    /// existing systems or stars it discovers from the bank on the way
+    pub(crate) fn distance(&self, from: SystemId, to: SystemId)->Option<u8>{
+        self.system(from)?;
+        self.system(to)?;
+        let discoverable: Vec<Node> = SIZES.into_iter()
+            .filter(|size|self.bank.iter().any(|id|id.size() == *size))
+            .map(Node::Discovery)
+            .collect();
+        let nodes: Vec<Node> = self.systems().map(|(id, _)|Node::System(id)).chain(discoverable).collect();
+
+        let mut seen = vec![false; nodes.len()];
+        let mut queue = VecDeque::from([(Node::System(from), 0)]);
+        while let Some((node, steps)) = queue.pop_front(){
+            if node == Node::System(to) {
+                return Some(steps);
+            }
+            let sizes = self.node_sizes(node);
+            for (i, next) in nodes.iter().enumerate(){
+                if seen[i] || *next == node {
+                    continue;
+                }
+                if self.node_sizes(*next).iter().all(|size|!sizes.contains(size)) {
+                    seen[i] = true;
+                    queue.push_back((*next, steps + 1));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn connections_and_distances(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Medium)], vec![]]),
+            system(vec![piece(Yellow, Small), piece(Green, Large)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+            system(vec![piece(Red, Large)], None, [vec![piece(Yellow, Large)], vec![]]),
+        ]);
+        let home_0 = board.homeworld(0).unwrap();
+        let home_1 = board.homeworld(1).unwrap();
+        let large = board.find_system(piece(Red, Large)).unwrap();
+
+        assert!(board.connected(home_0, large));
+        assert!(!board.connected(large, home_1));
+        assert!(!board.connected(home_0, home_1));
+        assert_eq!(board.neighbours(home_0), vec![large]);
+        assert_eq!(board.neighbours(home_1), vec![]);
+
+        assert_eq!(board.distance(home_0, large), Some(1));
+        // large star, then a discovered medium
+        assert_eq!(board.distance(home_0, home_1), Some(3));
+        assert_eq!(board.distance(large, home_1), Some(2));
+        assert_eq!(board.distance(home_0, home_0), Some(0));
+    }
+
+    #[test]
+    fn discoveries_need_bank_stars(){
+        use {Color::*, Size::*};
+        let mut board = board(vec![
+            system(vec![piece(Red, Small)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Yellow, Small)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let home_0 = board.homeworld(0).unwrap();
+        let home_1 = board.homeworld(1).unwrap();
+        assert_eq!(board.distance(home_0, home_1), Some(2));
+
+        // without medium and large stars left there is no way across
+        let bank: Vec<Id> = board.bank.iter().copied().filter(|id|id.size() != Small).collect();
+        let home = board.system_mut(home_1).unwrap();
+        home.ships[1].extend(bank.iter().copied());
+        for id in bank{
+            board.bank.remove(&id);
+        }
+        assert_eq!(board.distance(home_0, home_1), None);
+    }
+}
@@ -0,0 +1,219 @@
+use super::*;
+
+/// why the player to move can't take an action, see `Board::rules_help`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Blocked{
+    /// every player takes a homeworld before anything else
+    NoHomeworld,
+    /// actions are taken by a ship of your own in the system
+    NoShip,
+    /// no star or own ship of the color here, and no other ship of it to sacrifice
+    NoTechnology(Color),
+    NoEnemies,
+    /// your largest ship here and the smallest enemy ship
+    Outsized{ ours: Size, theirs: Size },
+    BankEmpty(Color),
+    /// no piece of the size in another color left in the bank
+    NoTrade(Size),
+    /// no connected system and no star of a new size in the bank
+    NowhereToGo,
+    /// the color with the most pieces here and how many there are
+    TooFew(Color, usize),
+}
+
+fn word(value: impl std::fmt::Debug)->String{
+    format!("{value:?}").to_lowercase()
+}
+
+impl std::fmt::Display for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Blocked::NoHomeworld => write!(f, "choose a homeworld first"),
+            Blocked::NoShip => write!(f, "you have no ship in this system"),
+            Blocked::NoTechnology(color) => {
+                let color = word(color);
+                write!(f, "no {color} star or ship of yours here, and no other {color} ship to sacrifice")
+            },
+            Blocked::NoEnemies => write!(f, "there are no enemy ships here"),
+            Blocked::Outsized { ours, theirs } => write!(f, "your largest ship here is {}, their smallest is {}", word(ours), word(theirs)),
+            Blocked::BankEmpty(color) => write!(f, "the bank has no {} pieces left", word(color)),
+            Blocked::NoTrade(size) => write!(f, "the bank has no {} piece of another color", word(size)),
+            Blocked::NowhereToGo => write!(f, "no system is connected to this one and the bank has no star of a new size"),
+            Blocked::TooFew(color, count) => write!(f, "at most {count} {} pieces here, four are needed", word(color)),
+        }
+    }
+}
+
+/// one kind of action at a system, for players asking why they can't do something
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RuleHelp{
+    /// `capture`, `build`, ...
+    pub(crate) name: &'static str,
+    /// the rule it follows
+    pub(crate) rule: &'static str,
+    /// the ways to do it here, each a short list of actions, or why there are none
+    pub(crate) actions: Result<Vec<Vec<Action>>, Blocked>,
+    /// only possible by sacrificing a ship of the color elsewhere
+    pub(crate) by_sacrifice: bool,
+}
+
+impl Board{
+    /// what the player to move can do at `system`: one entry per color of
+    /// the set, one for catastrophes and one for sacrificing `ship` if it is
+    /// theirs. With a ship selected only the actions it takes part in count.
+    pub(crate) fn rules_help(&self, system: SystemId, ship: Option<Id>)->Vec<RuleHelp>{
+        let player = self.current_player;
+        let Some(here) = self.system(system) else {
+            return Vec::new();
+        };
+        let star = here.stars[0];
+        let ours = &here.ships[player as usize];
+        let mine = ship.filter(|ship|ours.contains(ship));
+        let homeless = self.homeworld(player).is_none();
+        let mut help = Vec::new();
+
+        if let Some(ship) = mine {
+            help.push(RuleHelp {
+                name: "sacrifice",
+                rule: "give a ship back to the bank for one action of its color per size step, anywhere you have ships",
+                actions: Ok(vec![vec![Action::DestroyShip { ship, star }]]),
+                by_sacrifice: false,
+            });
+        }
+
+        for color in self.set.colors(){
+            let (name, rule) = match color{
+                Color::Red => ("capture", "red takes over an enemy ship no larger than your largest ship in the system"),
+                Color::Green => ("build", "green builds the smallest ship in the bank of a color you have a ship of in the system"),
+                Color::Blue => ("trade", "blue swaps one of your ships for one of the same size and another color from the bank"),
+                Color::Yellow => ("move", "yellow moves a ship to a system whose stars share no size with these, or to a new star from the bank"),
+            };
+            let direct = here.powers(player).contains(color);
+            // the sacrificed ship can't be the one acting or the last one here
+            let by_sacrifice = !direct && self.sacrificable(player, color)
+                .any(|sacrifice|Some(sacrifice) != mine && ours.iter().any(|ship|*ship != sacrifice));
+            let takes_part = |actions: &Vec<Action>|match (color, ship, mine){
+                (Color::Red, Some(ship), None) => actions.contains(&Action::CaptureShip { ship, star }),
+                (Color::Green, _, Some(mine)) => matches!(actions[..], [Action::CreateShip { ship, .. }] if ship.color() == mine.color()),
+                (Color::Blue, _, Some(mine)) => actions.contains(&Action::DestroyShip { ship: mine, star }),
+                (Color::Yellow, _, Some(mine)) => matches!(actions[..],
+                    [Action::MoveShip { ship, .. } | Action::CreateStar { ship, .. }] if ship == mine),
+                _ => true,
+            };
+            let actions = if homeless {
+                Err(Blocked::NoHomeworld)
+            }else if ours.is_empty() {
+                Err(Blocked::NoShip)
+            }else if !direct && !by_sacrifice {
+                Err(Blocked::NoTechnology(color))
+            }else{
+                let actions: Vec<Vec<Action>> = self.color_actions(system, color).into_iter().filter(takes_part).collect();
+                match actions.is_empty(){
+                    true => Err(self.blocked(here, color, ship, mine)),
+                    false => Ok(actions),
+                }
+            };
+            help.push(RuleHelp { name, rule, actions, by_sacrifice });
+        }
+
+        let catastrophes: Vec<Vec<Action>> = self.catastrophes().into_iter()
+            .filter(|action|matches!(action, Action::Catastrophe { star: at, .. } if *at == star))
+            .map(|action|vec![action])
+            .collect();
+        let actions = match (homeless, catastrophes.is_empty()){
+            (true, _) => Err(Blocked::NoHomeworld),
+            (false, true) => {
+                let (color, count) = self.set.colors()
+                    .map(|color|(color, here.color_count(color)))
+                    .max_by_key(|(_, count)|*count)
+                    .expect("a piece set has colors");
+                Err(Blocked::TooFew(color, count))
+            },
+            (false, false) => Ok(catastrophes),
+        };
+        help.push(RuleHelp {
+            name: "catastrophe",
+            rule: "four or more pieces of one color in a system, stars and ships alike, all go back to the bank",
+            actions,
+            by_sacrifice: false,
+        });
+        help
+    }
+
+    /// why the player to move has the technology for `color` here but nothing to use it on
+    fn blocked(&self, here: &System, color: Color, ship: Option<Id>, mine: Option<Id>)->Blocked{
+        let player = self.current_player as usize;
+        // only called with a ship of ours here
+        let acting = mine.unwrap_or(here.ships[player][0]);
+        match color{
+            Color::Red => {
+                let theirs = match (ship, mine){
+                    (Some(ship), None) => Some(ship.size()),
+                    _ => here.ships.iter().enumerate()
+                        .filter(|(owner, _)|*owner != player)
+                        .flat_map(|(_, ships)|ships.iter().map(|ship|ship.size()))
+                        .min(),
+                };
+                let ours = here.ships[player].iter().map(|ship|ship.size()).max();
+                match (ours, theirs){
+                    (Some(ours), Some(theirs)) => Blocked::Outsized { ours, theirs },
+                    _ => Blocked::NoEnemies,
+                }
+            },
+            Color::Green => Blocked::BankEmpty(acting.color()),
+            Color::Blue => Blocked::NoTrade(acting.size()),
+            Color::Yellow => Blocked::NowhereToGo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn help_explains_what_is_blocked(){
+        use {Color::*, Size::*};
+        let start = board(vec![
+            system(vec![piece(Red, Small), piece(Yellow, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Blue, Large)], None, [vec![piece(Blue, Small)], vec![piece(Red, Medium)]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Medium)]]),
+        ]);
+        let ids: Vec<SystemId> = start.systems().map(|(id, _)|id).collect();
+        let find = |help: &[RuleHelp], name: &str|help.iter().find(|help|help.name == name).cloned().unwrap();
+
+        let home = start.rules_help(ids[0], None);
+        assert_eq!(home.len(), 5);
+        assert_eq!(find(&home, "capture").actions, Err(Blocked::NoEnemies));
+        assert!(find(&home, "build").actions.is_ok());
+        // no blue at home, but the small blue elsewhere can be sacrificed
+        let trade = find(&home, "trade");
+        assert!(trade.actions.is_ok() && trade.by_sacrifice);
+        assert!(find(&home, "move").actions.is_ok());
+        assert_eq!(find(&home, "catastrophe").actions, Err(Blocked::TooFew(Yellow, 1)));
+
+        // nothing red at the blue star to capture their medium red with
+        let enemy = start.rules_help(ids[1], Some(piece(Red, Medium)));
+        assert_eq!(find(&enemy, "capture").actions, Err(Blocked::NoTechnology(Red)));
+
+        let own = start.rules_help(ids[1], Some(piece(Blue, Small)));
+        assert_eq!(own[0].name, "sacrifice");
+        // the large green at home is sacrificed to build another small blue
+        let build = find(&own, "build");
+        assert!(build.by_sacrifice);
+        assert_eq!(build.actions, Ok(vec![vec![Action::CreateShip { ship: Id::new(Blue, Small, 1), star: piece(Blue, Large) }]]));
+        assert_eq!(find(&own, "move").actions, Err(Blocked::NoTechnology(Yellow)));
+        assert_eq!(start.rules_help(ids[2], None)[0].actions, Err(Blocked::NoShip));
+
+        let outsized = board(vec![
+            system(vec![piece(Red, Small), piece(Yellow, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Red, Large)], None, [vec![piece(Green, Small)], vec![piece(Blue, Medium)]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Large)]]),
+        ]);
+        let ids: Vec<SystemId> = outsized.systems().map(|(id, _)|id).collect();
+        let capture = find(&outsized.rules_help(ids[1], None), "capture").actions;
+        assert_eq!(capture, Err(Blocked::Outsized { ours: Small, theirs: Medium }));
+        assert_eq!(capture.unwrap_err().to_string(), "your largest ship here is small, their smallest is medium");
+    }
+}
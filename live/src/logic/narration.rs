@@ -0,0 +1,393 @@
+use super::*;
+use super::game::Event;
+
+/// how the pieces of a clause are introduced
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Article{
+    /// the player's own ships, `their small red`
+    Own,
+    /// pieces coming out of the bank, `a small red`
+    New,
+    /// another player's ships, `Bob's small red`
+    Of(String),
+    /// written out in full already
+    Bare,
+}
+
+/// one thing done in a turn: `moves their small red and large green from home to the medium blue system`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause{
+    verb: &'static str,
+    article: Article,
+    pieces: Vec<String>,
+    /// what follows the pieces, starting with a space unless empty
+    tail: String,
+}
+
+impl Clause{
+    fn new(verb: &'static str, article: Article, piece: String, tail: String)->Clause{
+        Clause { verb, article, pieces: vec![piece], tail }
+    }
+}
+
+fn number(count: usize)->String{
+    match count{
+        2 => "two".to_string(),
+        3 => "three".to_string(),
+        4 => "four".to_string(),
+        5 => "five".to_string(),
+        6 => "six".to_string(),
+        _ => count.to_string(),
+    }
+}
+
+/// `a, b and c`
+fn join(items: &[String])->String{
+    match items{
+        [] => String::new(),
+        [item] => item.clone(),
+        [rest @ .., last] => format!("{} and {last}", rest.join(", ")),
+    }
+}
+
+impl std::fmt::Display for Clause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the same piece twice reads `two small reds`
+        let mut counted: Vec<(String, usize)> = Vec::new();
+        for piece in &self.pieces{
+            match counted.iter_mut().find(|(counted, _)|counted == piece){
+                Some((_, count)) => *count += 1,
+                None => counted.push((piece.clone(), 1)),
+            }
+        }
+        let pieces: Vec<String> = counted.into_iter().map(|(piece, count)|match (count, &self.article){
+            (1, Article::New) => format!("a {piece}"),
+            (1, _) => piece,
+            (count, _) => format!("{} {piece}s", number(count)),
+        }).collect();
+        let prefix = match &self.article{
+            Article::Own => "their ".to_string(),
+            Article::Of(name) => format!("{name}'s "),
+            Article::New | Article::Bare => String::new(),
+        };
+        write!(f, "{} {prefix}{}{}", self.verb, join(&pieces), self.tail)
+    }
+}
+
+/// names a system from the point of view of `player`
+struct Places<'n>{
+    player: u8,
+    names: &'n [String],
+}
+
+impl Places<'_>{
+    fn name(&self, player: u8)->String{
+        self.names.get(player as usize).cloned().unwrap_or_else(||format!("player {player}"))
+    }
+    /// `home`, `Bob's home` or `the small red system`
+    fn place(&self, board: &Board, star: Id)->String{
+        let home = board.find_system(star).and_then(|id|board.system(id)?.home);
+        match home{
+            Some(owner) if owner == self.player => "home".to_string(),
+            Some(owner) => format!("{}'s home", self.name(owner)),
+            None => format!("the {star} system"),
+        }
+    }
+    fn at(&self, board: &Board, star: Id)->String{
+        format!(" at {}", self.place(board, star))
+    }
+    fn journey(&self, board: &Board, start: Id, end: String)->String{
+        format!(" from {} {end}", self.place(board, start))
+    }
+    fn to(&self, board: &Board, star: Id)->String{
+        match self.place(board, star).as_str(){
+            "home" => "back home".to_string(),
+            place => format!("to {place}"),
+        }
+    }
+}
+
+/// `turn` taken on `before` in plain English, like `Alice sacrifices their
+/// large yellow at home and moves their small red and medium green from home
+/// to the small blue system, then sets off a green catastrophe at the small blue system`.
+/// Players are called by `names`, `player 1` for those without one.
+pub(crate) fn narrate_turn(before: &Board, turn: &[Action], names: &[String])->String{
+    let places = Places { player: before.current_player, names };
+    let name = places.name(before.current_player);
+    let mut board = before.clone();
+    let mut clauses: Vec<Clause> = Vec::new();
+    let mut catastrophes: Vec<Clause> = Vec::new();
+    let mut sacrifice = None;
+    let mut actions = turn.iter().copied().peekable();
+    while let Some(action) = actions.next(){
+        let mut played = vec![action];
+        let clause = match action{
+            Action::CreateHomeworld { stars, ship } => Clause::new("takes", Article::Bare,
+                format!("a {} and a {} star and a {ship} ship", stars[0], stars[1]), " as their homeworld".to_string()),
+            Action::CreateShip { ship, star } => match actions.peek(){
+                // blue trades build the new ship before the old one goes
+                Some(Action::DestroyShip { ship: old, star: old_star }) if *old_star == star && old.size() == ship.size() => {
+                    let old = *old;
+                    played.push(actions.next().expect("peeked"));
+                    Clause::new("trades", Article::Own, old.to_string(), format!(" for a {ship}{}", places.at(&board, star)))
+                },
+                _ => Clause::new("builds", Article::New, ship.to_string(), places.at(&board, star)),
+            },
+            Action::DestroyShip { ship, star } => {
+                let clause = Clause::new("sacrifices", Article::Own, ship.to_string(), places.at(&board, star));
+                if clauses.is_empty() && sacrifice.is_none() {
+                    sacrifice = Some(clause);
+                    board = board.clone().apply_action(action).unwrap_or(board);
+                    continue;
+                }
+                clause
+            },
+            Action::MoveShip { ship, start_star, end_star } => Clause::new("moves", Article::Own, ship.to_string(),
+                places.journey(&board, start_star, places.to(&board, end_star))),
+            Action::CreateStar { star, ship, start_star } => Clause::new("moves", Article::Own, ship.to_string(),
+                places.journey(&board, start_star, format!("to a new {star} star"))),
+            Action::DestroyStar { star } => Clause::new("collapses", Article::Bare, format!("the {star} star"), places.at(&board, star)),
+            Action::CaptureShip { ship, star } => {
+                let owner = board.find_system(star)
+                    .and_then(|id|board.system(id)?.ships.iter().position(|ships|ships.contains(&ship)))
+                    .unwrap_or_default();
+                Clause::new("captures", Article::Of(places.name(owner as u8)), ship.to_string(), places.at(&board, star))
+            },
+            Action::Catastrophe { star, color } => {
+                let color = format!("{color:?}").to_lowercase();
+                catastrophes.push(Clause::new("sets off", Article::Bare, format!("a {color} catastrophe"), places.at(&board, star)));
+                board = board.clone().apply_action(action).unwrap_or(board);
+                continue;
+            },
+        };
+        for action in played{
+            board = board.clone().apply_action(action).unwrap_or(board);
+        }
+        // moving two ships the same way is one clause
+        match clauses.last_mut(){
+            Some(last) if last.verb == clause.verb && last.article == clause.article && last.tail == clause.tail => {
+                last.pieces.extend(clause.pieces);
+            },
+            _ => clauses.push(clause),
+        }
+    }
+
+    let clauses: Vec<String> = clauses.iter().map(|clause|clause.to_string()).collect();
+    let mut text = match (&sacrifice, clauses.is_empty()){
+        (Some(sacrifice), true) => format!("{name} {sacrifice}"),
+        (Some(sacrifice), false) => format!("{name} {sacrifice} and {}", join(&clauses)),
+        (None, false) => format!("{name} {}", join(&clauses)),
+        (None, true) if catastrophes.is_empty() => format!("{name} passes"),
+        (None, true) => name.clone(),
+    };
+    if !catastrophes.is_empty() {
+        let catastrophes: Vec<String> = catastrophes.iter().map(|clause|clause.to_string()).collect();
+        let then = if text == name { "" } else { ", then" };
+        text += &format!("{then} {}", join(&catastrophes));
+    }
+    text
+}
+
+/// every turn of `record` narrated, see `narrate_turn`. Turns that don't
+/// replay are written as in the record.
+pub(crate) fn narrate(record: &Record, names: &[String])->Vec<String>{
+    let mut narration = Narration::default();
+    narration.update(record, names);
+    narration.lines
+}
+
+/// the narration of a game as it goes on, only new turns are narrated again
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Narration{
+    /// what was narrated: the players, the pieces and the turns so far
+    key: Option<(u8, PieceSet, Vec<String>)>,
+    turns: Vec<Vec<Action>>,
+    /// the decisions applied to `game`
+    events: Vec<Event>,
+    /// the game after the narrated turns, none once one didn't replay
+    game: Option<Game>,
+    lines: Vec<String>,
+}
+
+impl Narration{
+    /// catches up with `record`, starting over if it isn't the narrated
+    /// game with more turns and decisions. Decisions are applied where
+    /// they were made, as `Game::replay` does.
+    pub(crate) fn update(&mut self, record: &Record, names: &[String]){
+        let key = Some((record.player_count, record.set, names.to_vec()));
+        if self.key != key || !record.turns.starts_with(&self.turns) || !record.events.starts_with(&self.events) {
+            *self = Narration {
+                key,
+                turns: Vec::new(),
+                events: Vec::new(),
+                game: Some(Game::from_board(Board::with_set(record.player_count, record.set), None)),
+                lines: Vec::new(),
+            };
+        }
+        for turn in &record.turns[self.turns.len()..]{
+            self.decide(record, self.turns.len());
+            let text = match &mut self.game{
+                Some(played) => {
+                    let text = narrate_turn(played.board(), turn, names);
+                    if turn.iter().try_for_each(|action|played.apply_action(*action)).is_some() {
+                        played.end_turn();
+                    }else{
+                        self.game = None;
+                    }
+                    text
+                },
+                None => format_turn(turn),
+            };
+            self.turns.push(turn.clone());
+            self.lines.push(text);
+        }
+        self.decide(record, self.turns.len());
+    }
+
+    /// applies the decisions of `record` made up to the position before turn `turn`
+    fn decide(&mut self, record: &Record, turn: usize){
+        for event in record.events[self.events.len()..].iter().take_while(|event|event.turn <= turn){
+            if let Some(game) = &mut self.game {
+                if game.decide(event.player, event.decision).is_none() {
+                    self.game = None;
+                }
+            }
+            self.events.push(*event);
+        }
+    }
+    #[cfg(any(test, feature = "window"))]
+    pub(crate) fn lines(&self)->&[String]{
+        &self.lines
+    }
+}
+
+impl Record{
+    /// the record with every turn followed by a `#` line narrating it, which
+    /// `parse` skips
+    pub(crate) fn narrated(&self, names: &[String])->String{
+        let mut narrations = narrate(self, names).into_iter();
+        let mut narrated = String::new();
+        for line in self.to_string().lines(){
+            narrated += &format!("{line}\n");
+            // the header and decisions don't read as turns
+            if parse_turn(line).is_some() {
+                if let Some(narration) = narrations.next() {
+                    narrated += &format!("# {narration}\n");
+                }
+            }
+        }
+        narrated
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    fn names()->Vec<String>{
+        vec!["Alice".to_string(), "Bob".to_string()]
+    }
+
+    #[test]
+    fn turns_read_as_sentences(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Yellow, Medium)], Some(0), [vec![piece(Yellow, Large), piece(Green, Small), piece(Red, Medium)], vec![]]),
+            system(vec![piece(Blue, Large)], None, [vec![piece(Blue, Small)], vec![Id::new(Red, Small, 1)]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Medium)]]),
+        ]);
+        let narrate = |turn: &[Action]|narrate_turn(&board, turn, &names());
+        assert_eq!(narrate(&[]), "Alice passes");
+
+        let blue_large = piece(Blue, Large);
+        let home = piece(Red, Small);
+        assert_eq!(narrate(&[
+            Action::DestroyShip { ship: piece(Yellow, Large), star: home },
+            Action::MoveShip { ship: piece(Green, Small), start_star: home, end_star: blue_large },
+            Action::MoveShip { ship: piece(Red, Medium), start_star: home, end_star: blue_large },
+        ]), "Alice sacrifices their large yellow at home and moves their small green and medium red from home to the large blue system");
+
+        assert_eq!(narrate(&[Action::CaptureShip { ship: Id::new(Red, Small, 1), star: blue_large }]),
+            "Alice captures Bob's small red at the large blue system");
+        assert_eq!(narrate(&[
+            Action::CreateShip { ship: Id::new(Green, Small, 1), star: home },
+            Action::CreateShip { ship: Id::new(Green, Small, 2), star: home },
+        ]), "Alice builds two small greens at home");
+        assert_eq!(narrate(&[
+            Action::CreateShip { ship: Id::new(Red, Small, 2), star: blue_large },
+            Action::DestroyShip { ship: piece(Blue, Small), star: blue_large },
+        ]), "Alice trades their small blue for a small red at the large blue system");
+        assert_eq!(narrate(&[
+            Action::CreateStar { star: piece(Green, Large), ship: piece(Blue, Small), start_star: blue_large },
+        ]), "Alice moves their small blue from the large blue system to a new large green star");
+
+        // Bob's side, with a catastrophe to finish
+        let mut theirs = board.clone();
+        theirs.end_turn();
+        let turn = [
+            Action::MoveShip { ship: Id::new(Red, Small, 1), start_star: blue_large, end_star: piece(Yellow, Small) },
+            Action::Catastrophe { star: home, color: Red },
+        ];
+        assert_eq!(narrate_turn(&theirs, &turn, &names()),
+            "Bob moves their small red from the large blue system back home, then sets off a red catastrophe at Alice's home");
+        assert_eq!(narrate_turn(&theirs, &turn[1..], &[]), "player 1 sets off a red catastrophe at player 0's home");
+    }
+
+    #[test]
+    fn records_are_narrated_in_comments(){
+        use super::super::game::Event;
+        use {Color::*, Size::*};
+        let record = Record {
+            player_count: 2,
+            set: PieceSet::STANDARD,
+            result: None,
+            turns: vec![
+                vec![Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) }],
+                vec![],
+            ],
+            events: vec![Event { turn: 1, player: 1, decision: Decision::OfferDraw }],
+        };
+        let text = record.narrated(&names());
+        assert_eq!(text, "players 2\nhomeworld r1a b2a g3a\n\
+            # Alice takes a small red and a medium blue star and a large green ship as their homeworld\n\
+            decision 1 offer\npass\n# Bob passes\n");
+        assert_eq!(Record::parse(&text), Some(record.clone()));
+
+        // a game in progress is narrated a turn at a time, and again from the start once it is taken back
+        let mut narration = Narration::default();
+        let first = Record { turns: record.turns[..1].to_vec(), ..record.clone() };
+        narration.update(&first, &names());
+        assert_eq!(narration.lines().len(), 1);
+        narration.update(&record, &names());
+        assert_eq!(narration.lines(), narrate(&record, &names()));
+        let other = Record { turns: vec![vec![]], ..record };
+        narration.update(&other, &names());
+        assert_eq!(narration.lines(), narrate(&other, &names()));
+    }
+
+    #[test]
+    fn resignations_pass_the_turn_on(){
+        let names: Vec<String> = ["Alice", "Bob", "Carol"].iter().map(|name|name.to_string()).collect();
+        let play = |game: &mut Game|{
+            let (turn, _) = game.board().legal_turns().swap_remove(0);
+            for action in turn{
+                game.apply_action(action).unwrap();
+            }
+            game.end_turn();
+        };
+        let mut game = Game::new(3, None);
+        for _ in 0..3{
+            play(&mut game);
+        }
+        let mut narration = Narration::default();
+        narration.update(&game.record(), &names);
+        // Alice resigns on her turn, so Bob moves next
+        game.decide(0, Decision::Resign).unwrap();
+        play(&mut game);
+        narration.update(&game.record(), &names);
+        assert_eq!(narration.lines().len(), 4);
+        assert!(narration.lines()[3].starts_with("Bob "), "{}", narration.lines()[3]);
+        assert_eq!(narration.lines(), narrate(&game.record(), &names));
+    }
+}
@@ -0,0 +1,153 @@
+use super::*;
+
+/// the pieces a game is played with: some of the four colors, some of up to
+/// four sizes and one to three copies of each color and size. Written like
+/// `rgby/123/3`, the standard set.
+///
+/// That is as far as sets go. Colors are the technologies, a fifth one would
+/// need a power the rules don't have, so `Color` stays the four of them.
+/// Sizes and copies are capped by `SIZE_SLOTS` and `COPY_SLOTS`, which fix
+/// the layout of `Id` and `Position`. Sets beyond that are refused when they
+/// are read, with the limit they break.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PieceSet{
+    /// one bit per `Color`
+    colors: u8,
+    /// one bit per `Size`
+    sizes: u8,
+    copies: u8,
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        PieceSet::STANDARD
+    }
+}
+
+impl PieceSet{
+    pub(crate) const STANDARD: PieceSet = PieceSet { colors: 0b1111, sizes: 0b111, copies: 3 };
+
+    /// none without colors or sizes, or with more copies than ids have room for
+    pub(crate) fn new(colors: &[Color], sizes: &[Size], copies: u8)->Option<PieceSet>{
+        let colors = colors.iter().fold(0, |set, color|set | 1 << *color as u8);
+        let sizes = sizes.iter().fold(0, |set, size|set | 1 << *size as u8);
+        let valid = colors != 0 && sizes != 0 && (1..=COPY_SLOTS as u8).contains(&copies);
+        valid.then_some(PieceSet { colors, sizes, copies })
+    }
+
+    pub(crate) fn colors(self)->impl Iterator<Item = Color>{
+        COLORS.into_iter().filter(move |color|self.colors >> *color as u8 & 1 == 1)
+    }
+    /// smallest first
+    pub(crate) fn sizes(self)->impl Iterator<Item = Size>{
+        SIZES.into_iter().filter(move |size|self.sizes >> *size as u8 & 1 == 1)
+    }
+
+    pub(crate) fn contains(self, id: Id)->bool{
+        self.colors >> id.color() as u8 & 1 == 1 && self.sizes >> id.size() as u8 & 1 == 1 && id.copy() < self.copies
+    }
+    /// every piece of the set, in id order
+    pub(crate) fn ids(self)->impl Iterator<Item = Id>{
+        (0..ID_COUNT as u8).map(Id).filter(move |id|self.contains(*id))
+    }
+    /// the color and size combinations of the set, see `Id::kind`
+    pub(super) fn kinds(self)->impl Iterator<Item = usize>{
+        self.ids().filter(|id|id.copy() == 0).map(|id|id.kind())
+    }
+    #[cfg(any(test, feature = "window"))]
+    pub(crate) fn len(self)->usize{
+        (self.colors.count_ones() * self.sizes.count_ones()) as usize * self.copies as usize
+    }
+}
+
+impl std::fmt::Display for PieceSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for color in self.colors(){
+            write!(f, "{}", &format!("{color:?}").to_lowercase()[..1])?;
+        }
+        write!(f, "/")?;
+        for size in self.sizes(){
+            write!(f, "{}", size as u8 + 1)?;
+        }
+        write!(f, "/{}", self.copies)
+    }
+}
+
+impl std::str::FromStr for PieceSet {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = ||format!("expected colors, sizes and copies like `rgby/123/3`, found `{text}`");
+        let [colors, sizes, copies] = text.trim().split('/').collect::<Vec<_>>()[..] else {
+            return Err(error());
+        };
+        let colors = colors.chars()
+            .map(|initial|{
+                COLORS.into_iter().find(|color|format!("{color:?}").to_lowercase().starts_with(initial))
+                    .ok_or(format!("`{initial}` is not one of the colors r, g, b and y"))
+            })
+            .collect::<Result<Vec<Color>, String>>()?;
+        let sizes = sizes.chars()
+            .map(|digit|{
+                digit.to_digit(10).and_then(|digit|SIZES.get((digit as usize).checked_sub(1)?)).copied()
+                    .ok_or(format!("`{digit}` is not a size from 1 to {SIZE_SLOTS}"))
+            })
+            .collect::<Result<Vec<Size>, String>>()?;
+        let copies: u8 = copies.parse().map_err(|_|error())?;
+        if !(1..=COPY_SLOTS as u8).contains(&copies) {
+            return Err(format!("{copies} copies, sets have 1 to {COPY_SLOTS}"));
+        }
+        PieceSet::new(&colors, &sizes, copies).ok_or_else(error)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::position::Position;
+
+    #[test]
+    fn piece_sets_read_and_write(){
+        assert_eq!("rgby/123/3".parse(), Ok(PieceSet::STANDARD));
+        assert_eq!(PieceSet::STANDARD.len(), 36);
+        assert_eq!(PieceSet::STANDARD.ids().count(), 36);
+        assert_eq!(PieceSet::STANDARD.kinds().count(), 12);
+        assert!(PieceSet::STANDARD.ids().all(|id|id.size() != Size::Huge));
+
+        let set: PieceSet = "ygy/31/2".parse().unwrap();
+        assert_eq!(set.to_string(), "gy/13/2");
+        assert_eq!(set.ids().count(), set.len());
+        assert_eq!(set.len(), 8);
+        let board = Board::with_set(2, set);
+        assert_eq!(board.validate(), Ok(()));
+        assert_eq!(board.bank.len(), 8);
+
+        let large: PieceSet = "rgby/1234/3".parse().unwrap();
+        assert_eq!(large.len(), 48);
+        assert!(large.ids().all(|id|large.contains(id) && id.0 < ID_COUNT as u8));
+        for bad in ["rgb/123", "rgb/125/1", "rgb/123/4", "/123/1", "rgx/1/1"]{
+            assert!(bad.parse::<PieceSet>().is_err(), "{bad}");
+        }
+        // sets are limited to the four colors and the slots ids have
+        assert_eq!("rgbyp/1/1".parse::<PieceSet>(), Err("`p` is not one of the colors r, g, b and y".to_string()));
+        assert_eq!("rg/15/1".parse::<PieceSet>(), Err("`5` is not a size from 1 to 4".to_string()));
+        assert_eq!("rg/1/4".parse::<PieceSet>(), Err("4 copies, sets have 1 to 3".to_string()));
+    }
+
+    #[test]
+    fn a_fourth_size_plays_by_the_same_rules(){
+        let mut board = Board::with_set(2, "rgby/1234/2".parse().unwrap());
+        let mut rng = Rng::new(1);
+        for _ in 0..16{
+            let mut turns = board.legal_turns();
+            if turns.is_empty() {
+                break;
+            }
+            board = turns.swap_remove(rng.below(turns.len())).1;
+            board.end_turn();
+            assert_eq!(board.validate(), Ok(()));
+            let position = Position::from(&board);
+            assert_eq!(Position::from(&Board::from(position)), position);
+        }
+    }
+}
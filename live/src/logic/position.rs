@@ -0,0 +1,861 @@
+use std::collections::HashSet;
+
+use super::*;
+
+/// the most players a `Position` has room for
+const MAX_PLAYERS: usize = 4;
+
+/// a non-home system holds its star and at least one ship
+const MAX_STARS: usize = ID_COUNT / 2;
+
+/// number of pieces per kind, two bits per kind since there are at most 3 copies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+struct Counts(u32);
+impl Counts{
+    fn get(self, kind: usize)->u8{
+        (self.0 >> (kind * 2) & 0b11) as u8
+    }
+    fn add(&mut self, kind: usize)->Option<()>{
+        if self.get(kind) == 3 {
+            return None;
+        }
+        self.0 += 1 << (kind * 2);
+        Some(())
+    }
+    fn remove(&mut self, kind: usize)->Option<()>{
+        if self.get(kind) == 0 {
+            return None;
+        }
+        self.0 -= 1 << (kind * 2);
+        Some(())
+    }
+    /// adds all pieces of `other`, the caller makes sure no kind goes above 3
+    fn merge(&mut self, other: Counts){
+        self.0 += other.0;
+    }
+    fn is_empty(self)->bool{
+        self.0 == 0
+    }
+    /// the kinds there is at least one piece of, smallest id first
+    fn kinds(self)->impl Iterator<Item = usize>{
+        (0..KIND_COUNT).filter(move |kind|self.get(*kind) > 0)
+    }
+    fn color_len(self, color: Color)->usize{
+        (0..SIZE_SLOTS).map(|size|self.get(color as usize * SIZE_SLOTS + size) as usize).sum()
+    }
+    /// removes every piece of `color` and returns them
+    fn take_color(&mut self, color: Color)->Counts{
+        let mask = 0xff << (color as usize * SIZE_SLOTS * 2);
+        let taken = Counts(self.0 & mask);
+        self.0 &= !mask;
+        taken
+    }
+}
+
+/// the first id of a kind, what ship ids in `Position` actions stand for
+fn kind_id(kind: usize)->Id{
+    Id((kind * COPY_SLOTS) as u8)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct StarSystem{
+    star: Id,
+    ships: [Counts; MAX_PLAYERS],
+}
+impl StarSystem{
+    const EMPTY: Self = StarSystem { star: Id(0), ships: [Counts(0); MAX_PLAYERS] };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct HomeSystem{
+    /// in the same order as `System::stars`, used slots first
+    stars: [Option<Id>; 2],
+    ships: [Counts; MAX_PLAYERS],
+}
+impl HomeSystem{
+    const EMPTY: Self = HomeSystem { stars: [None; 2], ships: [Counts(0); MAX_PLAYERS] };
+}
+
+/// a system the way move generation looks at it, homeworld or not
+#[derive(Debug, Clone, Copy)]
+struct Local{
+    stars: [Option<Id>; 2],
+    ships: [Counts; MAX_PLAYERS],
+}
+impl Local{
+    /// the star actions name the system by
+    fn star(&self)->Id{
+        self.stars[0].expect("systems have a star")
+    }
+    fn star_ids(&self)->impl Iterator<Item = Id>{
+        self.stars.into_iter().flatten()
+    }
+    /// see `System::connected`
+    fn connected(&self, other: &Local)->bool{
+        self.star_ids().all(|a|other.star_ids().all(|b|a.size() != b.size()))
+    }
+    /// see `System::powers`
+    fn powers(&self, player: u8)->Powers{
+        self.star_ids().map(|star|star.color())
+            .chain(self.ships[player as usize].kinds().map(|kind|kind_id(kind).color()))
+            .collect()
+    }
+    /// see `System::color_count`
+    fn color_count(&self, color: Color)->usize{
+        self.star_ids().filter(|star|star.color() == color).count()
+            + self.ships.iter().map(|ships|ships.color_len(color)).sum::<usize>()
+    }
+}
+
+/// a system as it was before `Position::make` changed it
+#[derive(Debug, Clone, Copy)]
+enum Saved{
+    Home(u8, HomeSystem),
+    /// the system of the star, none if there wasn't one
+    Star(Id, Option<StarSystem>),
+}
+
+/// what `Position::make` changed, for `Position::unmake` to put back
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Undo{
+    current_player: u8,
+    bank: Counts,
+    /// no action touches more than two systems
+    saved: [Option<Saved>; 2],
+}
+
+/// an action, and for a capture whose ship it takes. Without an owner a
+/// capture takes from the next opponent in turn order that has the kind.
+type Step = (Action, Option<u8>);
+
+/// one use of a color: an action, or a build and the ship it replaces
+type Steps = [Option<Step>; 2];
+
+/// `Copy` version of `Board` that does not allocate, for search and as the
+/// key positions are hashed and compared by. Ships of the same color and
+/// size are interchangeable here, so boards that only differ in which copy
+/// of a kind is where are the same position, and ship ids in actions only
+/// select a kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Position{
+    current_player: u8,
+    player_count: u8,
+    set: PieceSet,
+    bank: Counts,
+    star_count: u8,
+    /// sorted by star, slots from `star_count` on are `StarSystem::EMPTY`
+    stars: [StarSystem; MAX_STARS],
+    /// slots from `player_count` on are `HomeSystem::EMPTY`
+    homeworlds: [HomeSystem; MAX_PLAYERS],
+}
+
+impl Position{
+    fn stars(&self)->&[StarSystem]{
+        &self.stars[..self.star_count as usize]
+    }
+    fn homeworlds(&self)->&[HomeSystem]{
+        &self.homeworlds[..self.player_count as usize]
+    }
+    fn find_star(&self, star: Id)->Result<usize, usize>{
+        self.stars().binary_search_by_key(&star, |system|system.star)
+    }
+    fn find_homeworld(&self, star: Id)->Option<usize>{
+        self.homeworlds().iter().position(|hw|hw.stars.contains(&Some(star)))
+    }
+    fn ships_mut(&mut self, star: Id)->Option<&mut [Counts; MAX_PLAYERS]>{
+        if let Some(i) = self.find_homeworld(star){
+            Some(&mut self.homeworlds[i].ships)
+        }else if let Ok(i) = self.find_star(star){
+            Some(&mut self.stars[i].ships)
+        }else{
+            None
+        }
+    }
+    fn insert_star(&mut self, i: usize, system: StarSystem){
+        let len = self.star_count as usize;
+        self.stars.copy_within(i..len, i+1);
+        self.stars[i] = system;
+        self.star_count += 1;
+    }
+    fn remove_star(&mut self, i: usize)->StarSystem{
+        let system = self.stars[i];
+        let len = self.star_count as usize;
+        self.stars.copy_within(i+1..len, i);
+        self.stars[len-1] = StarSystem::EMPTY;
+        self.star_count -= 1;
+        system
+    }
+    /// every system, homeworlds first
+    fn locals(&self)->impl Iterator<Item = Local> + '_{
+        self.homeworlds().iter()
+            .filter(|hw|hw.stars[0].is_some())
+            .map(|hw|Local { stars: hw.stars, ships: hw.ships })
+            .chain(self.stars().iter().map(|system|Local { stars: [Some(system.star), None], ships: system.ships }))
+    }
+    /// the lowest copy of `kind` in the set that isn't a star yet, for discoveries
+    fn free_star(&self, kind: usize)->Option<Id>{
+        (0..COPY_SLOTS as u8)
+            .map(|copy|Id(kind_id(kind).0 + copy))
+            .filter(|id|self.set.contains(*id))
+            .find(|id|self.find_star(*id).is_err() && self.find_homeworld(*id).is_none())
+    }
+
+    pub(super) fn current_player(&self)->u8{
+        self.current_player
+    }
+    /// `self` with `player` to move
+    pub(super) fn with_current_player(mut self, player: u8)->Self{
+        self.current_player = player;
+        self
+    }
+    pub(super) fn player_count(&self)->u8{
+        self.player_count
+    }
+    /// see `Board::end_turn`
+    pub(super) fn end_turn(&mut self){
+        self.current_player = (self.current_player + 1) % self.player_count;
+    }
+    pub(super) fn has_homeworld(&self, player: u8)->bool{
+        self.homeworlds[player as usize].stars[0].is_some()
+    }
+    /// see `lost_home`
+    pub(super) fn lost_home(&self, player: u8)->bool{
+        let home = &self.homeworlds[player as usize];
+        home.stars[0].is_none() || home.ships[player as usize].is_empty()
+    }
+
+    /// the system `star` belongs to as it is now
+    fn save(&self, star: Id)->Saved{
+        match self.find_homeworld(star){
+            Some(i) => Saved::Home(i as u8, self.homeworlds[i]),
+            None => Saved::Star(star, self.find_star(star).ok().map(|i|self.stars[i])),
+        }
+    }
+
+    /// plays `action` in place by the same rules as `Board::apply_action`,
+    /// none and unchanged if it is rejected. `unmake` takes it back.
+    pub(super) fn make(&mut self, action: Action)->Option<Undo>{
+        self.make_step((action, None))
+    }
+
+    fn make_step(&mut self, (action, owner): Step)->Option<Undo>{
+        let saved = match action{
+            Action::CreateHomeworld { .. } => {
+                let player = self.current_player;
+                [Some(Saved::Home(player, self.homeworlds[player as usize])), None]
+            },
+            Action::MoveShip { start_star, end_star, .. } => [Some(self.save(start_star)), Some(self.save(end_star))],
+            Action::CreateStar { star, start_star, .. } => [Some(self.save(start_star)), Some(self.save(star))],
+            Action::CreateShip { star, .. }
+            | Action::DestroyShip { star, .. }
+            | Action::CaptureShip { star, .. }
+            | Action::Catastrophe { star, .. }
+            | Action::DestroyStar { star } => [Some(self.save(star)), None],
+        };
+        let undo = Undo { current_player: self.current_player, bank: self.bank, saved };
+        if self.apply(action, owner).is_none() {
+            self.unmake(undo);
+            return None;
+        }
+        Some(undo)
+    }
+
+    /// puts back what `make` changed, the last action made first
+    pub(super) fn unmake(&mut self, undo: Undo){
+        self.current_player = undo.current_player;
+        self.bank = undo.bank;
+        for saved in undo.saved.into_iter().rev().flatten(){
+            match saved{
+                Saved::Home(player, home) => self.homeworlds[player as usize] = home,
+                Saved::Star(star, before) => match (self.find_star(star), before){
+                    (Ok(i), Some(system)) => self.stars[i] = system,
+                    (Ok(i), None) => {
+                        self.remove_star(i);
+                    },
+                    (Err(i), Some(system)) => self.insert_star(i, system),
+                    (Err(_), None) => {},
+                },
+            }
+        }
+    }
+
+    /// may leave `self` half changed when it fails
+    fn apply(&mut self, action: Action, owner: Option<u8>)->Option<()>{
+        let player_i = self.current_player as usize;
+        match action{
+            Action::CreateHomeworld { stars, ship } => {
+                if self.homeworlds[player_i].stars[0].is_some() || stars[0] == stars[1] {
+                    return None;
+                }
+                for star in stars{
+                    if !self.set.contains(star) || self.find_star(star).is_ok() || self.find_homeworld(star).is_some() {
+                        return None;
+                    }
+                    self.bank.remove(star.kind())?;
+                }
+                self.bank.remove(ship.kind())?;
+                let hw = &mut self.homeworlds[player_i];
+                hw.stars = [Some(stars[0]), Some(stars[1])];
+                hw.ships[player_i].add(ship.kind())?;
+            },
+            Action::CreateShip { ship, star } => {
+                self.bank.remove(ship.kind())?;
+                self.ships_mut(star)?[player_i].add(ship.kind())?;
+            },
+            Action::DestroyShip { ship, star } => {
+                self.ships_mut(star)?[player_i].remove(ship.kind())?;
+                self.bank.add(ship.kind())?;
+
+                // a system nobody occupies anymore goes back to the bank
+                if let Ok(i) = self.find_star(star){
+                    if self.stars[i].ships.iter().all(|ships|ships.is_empty()){
+                        self.remove_star(i);
+                        self.bank.add(star.kind())?;
+                    }
+                }
+            },
+            Action::MoveShip { ship, start_star, end_star } => {
+                self.apply(Action::DestroyShip { ship, star: start_star }, None)?;
+                self.apply(Action::CreateShip { ship, star: end_star }, None)?;
+            },
+            Action::CreateStar { star, ship, start_star } => {
+                let i = self.find_star(star).err()?;
+                if !self.set.contains(star) || self.find_homeworld(star).is_some() || self.star_count as usize == MAX_STARS {
+                    return None;
+                }
+                self.bank.remove(star.kind())?;
+                self.insert_star(i, StarSystem { star, ships: [Counts(0); MAX_PLAYERS] });
+                self.apply(Action::MoveShip { ship, start_star, end_star: star }, None)?;
+            },
+            Action::CaptureShip { ship, star } => {
+                let player_count = self.player_count as usize;
+                let ships = self.ships_mut(star)?;
+                let owner = match owner{
+                    Some(owner) => Some(owner as usize).filter(|owner|*owner != player_i)?,
+                    None => (1..player_count)
+                        .map(|i|(player_i + i) % player_count)
+                        .find(|owner|ships[*owner].get(ship.kind()) > 0)?,
+                };
+                ships[owner].remove(ship.kind())?;
+                ships[player_i].add(ship.kind())?;
+            },
+            Action::Catastrophe { star, color } => {
+                if let Ok(i) = self.find_star(star){
+                    let system = &mut self.stars[i];
+                    let count = (system.star.color() == color) as usize
+                        + system.ships.iter().map(|ships|ships.color_len(color)).sum::<usize>();
+                    if count < 4 {
+                        return None;
+                    }
+                    let mut taken = Counts(0);
+                    for ships in system.ships.iter_mut(){
+                        taken.merge(ships.take_color(color));
+                    }
+                    self.bank.merge(taken);
+                    let system = self.stars[i];
+                    if system.star.color() == color || system.ships.iter().all(|ships|ships.is_empty()) {
+                        let system = self.remove_star(i);
+                        for ships in system.ships{
+                            self.bank.merge(ships);
+                        }
+                        self.bank.add(system.star.kind())?;
+                    }
+                }else{
+                    let i = self.find_homeworld(star)?;
+                    let hw = &mut self.homeworlds[i];
+                    let count = hw.stars.iter().flatten().filter(|star|star.color() == color).count()
+                        + hw.ships.iter().map(|ships|ships.color_len(color)).sum::<usize>();
+                    if count < 4 {
+                        return None;
+                    }
+                    let mut taken = Counts(0);
+                    for ships in hw.ships.iter_mut(){
+                        taken.merge(ships.take_color(color));
+                    }
+                    for star in hw.stars.into_iter().flatten().filter(|star|star.color() == color){
+                        taken.add(star.kind())?;
+                    }
+                    let mut kept = hw.stars.into_iter().flatten().filter(|star|star.color() != color);
+                    hw.stars = [kept.next(), kept.next()];
+                    if hw.stars[0].is_none() {
+                        for ships in std::mem::take(&mut hw.ships){
+                            taken.merge(ships);
+                        }
+                    }
+                    self.bank.merge(taken);
+                }
+            },
+            Action::DestroyStar { star } => {
+                if let Ok(i) = self.find_star(star){
+                    let system = self.remove_star(i);
+                    for ships in system.ships{
+                        self.bank.merge(ships);
+                    }
+                    self.bank.add(star.kind())?;
+                }else{
+                    let i = self.find_homeworld(star)?;
+                    let hw = &mut self.homeworlds[i];
+                    if hw.stars[0] == Some(star) {
+                        hw.stars = [hw.stars[1], None];
+                    }else{
+                        hw.stars[1] = None;
+                    }
+                    if hw.stars[0].is_none() {
+                        for ships in std::mem::take(&mut hw.ships){
+                            self.bank.merge(ships);
+                        }
+                    }
+                    self.bank.add(star.kind())?;
+                }
+            },
+        }
+        Some(())
+    }
+
+    /// `steps` made after `played` and taken back again, with the position
+    /// they lead to. None if one is rejected.
+    fn play(&mut self, played: &[Action], steps: Steps)->Option<(Vec<Action>, Position)>{
+        let mut undos = [None; 2];
+        let mut made = true;
+        for (undo, step) in undos.iter_mut().zip(steps){
+            let Some(step) = step else {
+                break;
+            };
+            *undo = self.make_step(step);
+            if undo.is_none() {
+                made = false;
+                break;
+            }
+        }
+        let after = made.then(||{
+            let actions = played.iter().copied().chain(steps.into_iter().flatten().map(|(action, _)|action)).collect();
+            (actions, *self)
+        });
+        for undo in undos.into_iter().rev().flatten(){
+            self.unmake(undo);
+        }
+        after
+    }
+
+    /// see `Board::color_actions`
+    fn color_actions(&self, here: &Local, color: Color)->Vec<Steps>{
+        let player = self.current_player;
+        let star = here.star();
+        let ours = here.ships[player as usize];
+        let mut actions = Vec::new();
+        match color{
+            Color::Red => {
+                let Some(biggest) = ours.kinds().map(|kind|kind_id(kind).size()).max() else {
+                    return actions;
+                };
+                for owner in (0..self.player_count).filter(|owner|*owner != player){
+                    for kind in here.ships[owner as usize].kinds().filter(|kind|kind_id(*kind).size() <= biggest){
+                        actions.push([Some((Action::CaptureShip { ship: kind_id(kind), star }, Some(owner))), None]);
+                    }
+                }
+            },
+            Color::Green => {
+                let colors: Powers = ours.kinds().map(|kind|kind_id(kind).color()).collect();
+                for ship_color in colors.iter(){
+                    // always the smallest size left in the bank
+                    if let Some(kind) = self.bank.kinds().find(|kind|kind_id(*kind).color() == ship_color) {
+                        actions.push([Some((Action::CreateShip { ship: kind_id(kind), star }, None)), None]);
+                    }
+                }
+            },
+            Color::Blue => {
+                for kind in ours.kinds(){
+                    let ship = kind_id(kind);
+                    for new_kind in self.bank.kinds(){
+                        let new_ship = kind_id(new_kind);
+                        if new_ship.size() == ship.size() && new_ship.color() != ship.color() {
+                            actions.push([
+                                Some((Action::CreateShip { ship: new_ship, star }, None)),
+                                Some((Action::DestroyShip { ship, star }, None)),
+                            ]);
+                        }
+                    }
+                }
+            },
+            Color::Yellow => {
+                let discoveries: Vec<Id> = self.bank.kinds()
+                    .filter(|kind|here.star_ids().all(|star|star.size() != kind_id(*kind).size()))
+                    .filter_map(|kind|self.free_star(kind))
+                    .collect();
+                for kind in ours.kinds(){
+                    let ship = kind_id(kind);
+                    for other in self.locals(){
+                        if other.star() != star && here.connected(&other) {
+                            actions.push([Some((Action::MoveShip { ship, start_star: star, end_star: other.star() }, None)), None]);
+                        }
+                    }
+                    for new_star in &discoveries{
+                        actions.push([Some((Action::CreateStar { star: *new_star, ship, start_star: star }, None)), None]);
+                    }
+                }
+            },
+        }
+        actions
+    }
+
+    /// see `Board::catastrophes`
+    fn catastrophes(&self)->Vec<Action>{
+        self.locals()
+            .flat_map(|system|COLORS.into_iter()
+                .filter(move |color|system.color_count(*color) >= 4)
+                .map(move |color|Action::Catastrophe { star: system.star(), color }))
+            .collect()
+    }
+
+    /// the same turns as `Board::legal_turns`, made and taken back on one
+    /// position rather than cloning boards. Ship ids only say the kind, so
+    /// the turns are for showing; a `Board` has its own. Picking a homeworld
+    /// goes through `Board`.
+    pub(super) fn legal_turns(&self)->Vec<(Vec<Action>, Position)>{
+        let player = self.current_player;
+        if !self.has_homeworld(player) {
+            return Board::from(*self).legal_turns().into_iter()
+                .map(|(turn, board)|(turn, Position::from(&board)))
+                .collect();
+        }
+        let occupied = |position: &Position|->Vec<Local>{
+            position.locals().filter(|system|!system.ships[player as usize].is_empty()).collect()
+        };
+
+        let mut scratch = *self;
+        let mut turns = vec![(Vec::new(), *self)];
+        for here in occupied(self){
+            for color in here.powers(player).iter(){
+                for steps in self.color_actions(&here, color){
+                    turns.extend(scratch.play(&[], steps));
+                }
+            }
+        }
+
+        let sacrifices: Vec<(Id, Id)> = occupied(self).iter()
+            .flat_map(|system|system.ships[player as usize].kinds().map(|kind|(kind_id(kind), system.star())))
+            .collect();
+        for (ship, star) in sacrifices{
+            let Some(start) = scratch.play(&[], [Some((Action::DestroyShip { ship, star }, None)), None]) else {
+                continue;
+            };
+            // breadth first over what is left of the sacrifice
+            let mut layer = vec![start];
+            for _ in 0..ship.size() as usize + 1{
+                let mut seen = HashSet::new();
+                let mut next = Vec::new();
+                for (actions, position) in &layer{
+                    let mut scratch = *position;
+                    for here in occupied(position){
+                        for steps in position.color_actions(&here, ship.color()){
+                            if let Some((actions, after)) = scratch.play(actions, steps) {
+                                if seen.insert(after) {
+                                    next.push((actions, after));
+                                }
+                            }
+                        }
+                    }
+                }
+                turns.append(&mut layer);
+                layer = next;
+            }
+            turns.append(&mut layer);
+        }
+
+        let with_catastrophes: Vec<_> = turns.iter().filter_map(|(actions, position)|{
+            let catastrophes = position.catastrophes();
+            if catastrophes.is_empty() {
+                return None;
+            }
+            // one catastrophe can end another, so skip those that no longer apply
+            let mut actions = actions.clone();
+            let mut position = *position;
+            for catastrophe in catastrophes{
+                if position.make(catastrophe).is_some() {
+                    actions.push(catastrophe);
+                }
+            }
+            Some((actions, position))
+        }).collect();
+        turns.extend(with_catastrophes);
+
+        let mut seen = HashSet::new();
+        turns.retain(|(_, position)|seen.insert(*position));
+        turns
+    }
+}
+
+/// only the slots in use, the empty ones are all alike
+impl std::hash::Hash for Position {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.current_player, self.player_count, self.set, self.bank).hash(state);
+        self.stars().hash(state);
+        self.homeworlds().hash(state);
+    }
+}
+
+impl From<&Board> for Position{
+    fn from(board: &Board) -> Self {
+        let count = |ships: &[Vec<Id>]|{
+            let mut counts = [Counts(0); MAX_PLAYERS];
+            for (player, ships) in ships.iter().enumerate(){
+                for ship in ships{
+                    counts[player].add(ship.kind());
+                }
+            }
+            counts
+        };
+
+        let mut bank = Counts(0);
+        for id in &board.bank{
+            bank.add(id.kind());
+        }
+        let mut stars = [StarSystem::EMPTY; MAX_STARS];
+        let mut star_count = 0;
+        let mut homeworlds = [HomeSystem::EMPTY; MAX_PLAYERS];
+        for (_, system) in board.systems(){
+            match system.home{
+                Some(player) => {
+                    homeworlds[player as usize] = HomeSystem {
+                        stars: [system.stars.first().copied(), system.stars.get(1).copied()],
+                        ships: count(&system.ships),
+                    };
+                },
+                None => {
+                    stars[star_count] = StarSystem { star: system.stars[0], ships: count(&system.ships) };
+                    star_count += 1;
+                },
+            }
+        }
+        stars[..star_count].sort_by_key(|system|system.star);
+        Position {
+            current_player: board.current_player,
+            player_count: board.player_count,
+            set: board.set,
+            bank,
+            star_count: star_count as u8,
+            stars,
+            homeworlds,
+        }
+    }
+}
+
+impl From<Position> for Board{
+    /// ships get the lowest ids of their kind not used as a star
+    fn from(position: Position) -> Self {
+        let star_ids = position.stars().iter().map(|system|system.star)
+            .chain(position.homeworlds().iter().flat_map(|hw|hw.stars.into_iter().flatten()));
+        let mut free = [false; ID_COUNT];
+        for id in position.set.ids(){
+            free[id.0 as usize] = true;
+        }
+        for star in star_ids{
+            free[star.0 as usize] = false;
+        }
+        let mut take = |kind: usize|{
+            let i = (kind*COPY_SLOTS..(kind+1)*COPY_SLOTS).find(|i|free[*i]).expect("more pieces of a kind than copies");
+            free[i] = false;
+            Id(i as u8)
+        };
+        let mut ships = |counts: &[Counts; MAX_PLAYERS]|{
+            counts[..position.player_count as usize].iter().map(|counts|{
+                (0..KIND_COUNT)
+                    .flat_map(|kind|std::iter::repeat_n(kind, counts.get(kind) as usize))
+                    .map(&mut take)
+                    .collect::<Vec<Id>>()
+            }).collect::<Box<[Vec<Id>]>>()
+        };
+
+        let homeworlds = position.homeworlds().iter().enumerate()
+            .filter(|(_, hw)|hw.stars[0].is_some())
+            .map(|(player, hw)|System {
+                stars: hw.stars.into_iter().flatten().collect(),
+                home: Some(player as u8),
+                ships: ships(&hw.ships),
+            })
+            .collect::<Vec<_>>();
+        let stars = position.stars().iter()
+            .map(|system|System { stars: vec![system.star], home: None, ships: ships(&system.ships) })
+            .collect::<Vec<_>>();
+
+        let mut board = Board {
+            current_player: position.current_player,
+            player_count: position.player_count,
+            set: position.set,
+            bank: (0..ID_COUNT).filter(|i|free[*i]).map(|i|Id(i as u8)).collect(),
+            systems: Vec::new(),
+        };
+        for system in homeworlds.into_iter().chain(stars){
+            board.insert_system(system);
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::*;
+
+    #[test]
+    fn positions_round_trip(){
+        for seed in 0..500{
+            let mut rng = Rng::new(seed);
+            let player_count = 2 + rng.below(3) as u8;
+            let mut board = random_start(player_count, &mut rng);
+
+            for step in 0..100{
+                board.current_player = rng.below(player_count as usize) as u8;
+                let actions = legal_actions(&board);
+                if actions.is_empty(){
+                    break;
+                }
+                let action = rng.pick(&actions);
+                board = board.apply_action(action).unwrap();
+                let position = Position::from(&board);
+                let round_trip = Board::from(position);
+                assert_eq!(round_trip.validate(), Ok(()), "seed {seed}, step {step}: {action:?}");
+                assert_eq!(Position::from(&round_trip), position, "seed {seed}, step {step}: {action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn position_follows_board(){
+        for seed in 0..500{
+            let mut rng = Rng::new(seed);
+            let player_count = 2 + rng.below(3) as u8;
+            let mut board = random_start(player_count, &mut rng);
+            let mut position = Position::from(&board);
+
+            for step in 0..100{
+                board.current_player = rng.below(player_count as usize) as u8;
+                position.current_player = board.current_player;
+                let actions = legal_actions(&board);
+                if actions.is_empty(){
+                    break;
+                }
+                let action = rng.pick(&actions);
+                // the board knows whose ship a capture takes, the position is told
+                let owner = match action{
+                    Action::CaptureShip { ship, star } => board.system(board.find_system(star).unwrap()).unwrap().ships.iter()
+                        .position(|ships|ships.contains(&ship))
+                        .map(|owner|owner as u8),
+                    _ => None,
+                };
+                board = board.apply_action(action).unwrap();
+                let before = position;
+                let undo = position.make_step((action, owner))
+                    .unwrap_or_else(||panic!("seed {seed}, step {step}: {action:?} was rejected"));
+                assert_eq!(position, Position::from(&board), "seed {seed}, step {step}: {action:?}");
+
+                let mut undone = position;
+                undone.unmake(undo);
+                assert_eq!(undone, before, "seed {seed}, step {step}: {action:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejected_actions_leave_the_position_alone(){
+        use {Color::*, Size::*};
+        let mut position = Position::from(&two_homes());
+        let before = position;
+        let home = piece(Red, Small);
+        assert!(position.make(Action::DestroyShip { ship: piece(Blue, Large), star: home }).is_none());
+        // the ship leaves home before the move finds there is no such star
+        assert!(position.make(Action::MoveShip { ship: piece(Green, Large), start_star: home, end_star: piece(Red, Large) }).is_none());
+        assert_eq!(position, before);
+    }
+
+    #[test]
+    fn turns_match_the_board(){
+        // listing every turn of a crowded board takes a while, a few boards are enough
+        for seed in 0..16{
+            let mut rng = Rng::new(seed);
+            let player_count = 2 + rng.below(3) as u8;
+            let mut board = random_start(player_count, &mut rng);
+            for step in 0..8{
+                board.current_player = rng.below(player_count as usize) as u8;
+                let actions = legal_actions(&board);
+                if actions.is_empty(){
+                    break;
+                }
+                board = board.apply_action(rng.pick(&actions)).unwrap();
+                if step % 4 != 3 {
+                    continue;
+                }
+                // discoveries may take another copy of the star, so compare layouts
+                let layouts = |layouts: Vec<_>|{
+                    let mut layouts = layouts;
+                    layouts.sort();
+                    layouts.dedup();
+                    layouts
+                };
+                let from_board = layouts(board.legal_turns().iter().map(|(_, after)|after.layout()).collect());
+                let turns = Position::from(&board).legal_turns();
+                let from_position = layouts(turns.iter().map(|(_, after)|Board::from(*after).layout()).collect());
+                let missing: Vec<_> = from_board.iter().filter(|layout|!from_position.contains(layout)).collect();
+                let extra: Vec<_> = from_position.iter().filter(|layout|!from_board.contains(layout)).collect();
+                assert!(missing.is_empty() && extra.is_empty(), "seed {seed}, step {step}: {board}\nmissing {missing:?}\nextra {extra:?}");
+            }
+        }
+    }
+
+    /// how much faster turns are listed on positions than on boards:
+    /// `cargo test --release -p live position_speed -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn position_speed(){
+        use std::time::Instant;
+        // boards from games the engine plays against itself
+        let mut boards = Vec::new();
+        for seed in 0..4{
+            let mut engine = super::super::Engine::new(super::super::Weights::default(), 1.0, seed);
+            let mut board = Board::new(2);
+            for _ in 0..16{
+                let turn = engine.search(&board, None, super::super::Limits::default()).turn;
+                board = board.legal_turn(&turn).unwrap();
+                board.end_turn();
+                boards.push(board.clone());
+            }
+        }
+        let positions: Vec<Position> = boards.iter().map(Position::from).collect();
+
+        let start = Instant::now();
+        let mut board_turns = 0;
+        for board in &boards{
+            for (_, after) in board.legal_turns(){
+                let mut next = after.clone();
+                next.end_turn();
+                board_turns += next.legal_turns().len();
+            }
+        }
+        let on_boards = start.elapsed();
+        let start = Instant::now();
+        let mut position_turns = 0;
+        for position in &positions{
+            for (_, after) in position.legal_turns(){
+                let mut next = after;
+                next.end_turn();
+                position_turns += next.legal_turns().len();
+            }
+        }
+        let on_positions = start.elapsed();
+        println!("two turns deep from {} boards: {board_turns} turns in {on_boards:?} on boards, {position_turns} in {on_positions:?} on positions, {:.1}x",
+            boards.len(), on_boards.as_secs_f64() / on_positions.as_secs_f64());
+        assert!(on_positions < on_boards);
+    }
+
+    #[test]
+    fn copies_are_interchangeable(){
+        use {Color::*, Size::*};
+        let homeworld = |ship|Action::CreateHomeworld { stars: [Id::new(Red, Medium, 0), Id::new(Blue, Small, 2)], ship };
+        let first = Board::new(3).apply_action(homeworld(Id::new(Yellow, Large, 0))).unwrap();
+        let last = Board::new(3).apply_action(homeworld(Id::new(Yellow, Large, 2))).unwrap();
+        assert_ne!(first, last);
+        assert_eq!(Position::from(&first), Position::from(&last));
+        let other = Board::new(3).apply_action(homeworld(Id::new(Yellow, Medium, 0))).unwrap();
+        assert_ne!(Position::from(&first), Position::from(&other));
+    }
+}
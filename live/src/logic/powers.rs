@@ -0,0 +1,78 @@
+use super::*;
+
+/// a set of colors, one bit per color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub(crate) struct Powers(u8);
+
+impl Powers{
+    pub(crate) fn contains(self, color: Color)->bool{
+        self.0 & 1 << color as u8 != 0
+    }
+    pub(crate) fn insert(&mut self, color: Color){
+        self.0 |= 1 << color as u8;
+    }
+    #[cfg(test)]
+    pub(crate) fn is_empty(self)->bool{
+        self.0 == 0
+    }
+    pub(crate) fn iter(self)->impl Iterator<Item = Color>{
+        COLORS.into_iter().filter(move |color|self.contains(*color))
+    }
+}
+impl FromIterator<Color> for Powers {
+    fn from_iter<T: IntoIterator<Item = Color>>(iter: T) -> Self {
+        let mut powers = Powers::default();
+        for color in iter{
+            powers.insert(color);
+        }
+        powers
+    }
+}
+
+impl Board{
+    /// colors `player` can use at `system`, from its stars and the player's
+    /// own ships there. Without a ship there the player can't act at all.
+    pub(crate) fn available_powers(&self, player: u8, system: SystemId)->Powers{
+        match self.system(system){
+            Some(system) if !system.ships[player as usize].is_empty() => system.powers(player),
+            _ => Powers::default(),
+        }
+    }
+
+    /// for every player, the colors they can use in at least one system
+    pub(crate) fn power_summary(&self)->Vec<Powers>{
+        (0..self.player_count).map(|player|{
+            self.systems()
+                .flat_map(|(id, _)|self.available_powers(player, id).iter())
+                .collect()
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn powers_come_from_stars_and_own_ships(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large)], vec![piece(Yellow, Large)]]),
+            system(vec![piece(Yellow, Small), piece(Blue, Large)], Some(1), [vec![], vec![piece(Red, Large)]]),
+        ]);
+        let home_0 = board.homeworld(0).unwrap();
+        let home_1 = board.homeworld(1).unwrap();
+
+        let powers: Vec<Color> = board.available_powers(0, home_0).iter().collect();
+        assert_eq!(powers, vec![Red, Green, Blue]);
+        let powers: Vec<Color> = board.available_powers(1, home_0).iter().collect();
+        assert_eq!(powers, vec![Red, Blue, Yellow]);
+        // no ship, no actions
+        assert!(board.available_powers(0, home_1).is_empty());
+
+        let summary = board.power_summary();
+        assert_eq!(summary[0], [Red, Green, Blue].into_iter().collect());
+        assert_eq!(summary[1], [Red, Blue, Yellow].into_iter().collect());
+    }
+}
@@ -0,0 +1,255 @@
+use super::*;
+use super::game::Event;
+
+const COPIES: [char; COPY_SLOTS] = ['a', 'b', 'c'];
+
+/// `r1a`, `y3c`, ...: color initial, size from 1 to 3, or 4 in sets with a
+/// fourth size, and which of the three copies
+pub(crate) fn piece_code(id: Id)->String{
+    let color = format!("{:?}", id.color()).to_lowercase();
+    format!("{}{}{}", &color[..1], id.size() as u8 + 1, COPIES[id.copy() as usize])
+}
+
+pub(super) fn parse_piece(code: &str)->Option<Id>{
+    let mut chars = code.chars();
+    let initial = chars.next()?;
+    let color = COLORS.into_iter().find(|color|format!("{color:?}").to_lowercase().starts_with(initial))?;
+    let size = *SIZES.get((chars.next()?.to_digit(10)? as usize).checked_sub(1)?)?;
+    let copy = chars.next()?;
+    let copy = COPIES.iter().position(|c|*c == copy)?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(Id::new(color, size, copy as u8))
+}
+
+/// one action per line in game records, pieces as in `piece_code`
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = |id: &Id|piece_code(*id);
+        match self{
+            Action::CreateHomeworld { stars, ship } => write!(f, "homeworld {} {} {}", code(&stars[0]), code(&stars[1]), code(ship)),
+            Action::CreateShip { ship, star } => write!(f, "build {} {}", code(ship), code(star)),
+            Action::DestroyShip { ship, star } => write!(f, "destroy {} {}", code(ship), code(star)),
+            Action::MoveShip { ship, start_star, end_star } => write!(f, "move {} {} {}", code(ship), code(start_star), code(end_star)),
+            Action::CreateStar { star, ship, start_star } => write!(f, "discover {} {} {}", code(star), code(ship), code(start_star)),
+            Action::DestroyStar { star } => write!(f, "collapse {}", code(star)),
+            Action::CaptureShip { ship, star } => write!(f, "capture {} {}", code(ship), code(star)),
+            Action::Catastrophe { star, color } => write!(f, "catastrophe {} {}", format!("{color:?}").to_lowercase(), code(star)),
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let verb = words.next().ok_or(())?;
+        if verb == "catastrophe" {
+            let color = words.next().ok_or(())?;
+            let color = COLORS.into_iter().find(|c|format!("{c:?}").to_lowercase() == color).ok_or(())?;
+            let star = words.next().and_then(parse_piece).ok_or(())?;
+            return match words.next(){
+                None => Ok(Action::Catastrophe { star, color }),
+                Some(_) => Err(()),
+            };
+        }
+        let pieces = words.map(parse_piece).collect::<Option<Vec<Id>>>().ok_or(())?;
+        let action = match (verb, pieces.as_slice()){
+            ("homeworld", &[a, b, ship]) => Action::CreateHomeworld { stars: [a, b], ship },
+            ("build", &[ship, star]) => Action::CreateShip { ship, star },
+            ("destroy", &[ship, star]) => Action::DestroyShip { ship, star },
+            ("move", &[ship, start_star, end_star]) => Action::MoveShip { ship, start_star, end_star },
+            ("discover", &[star, ship, start_star]) => Action::CreateStar { star, ship, start_star },
+            ("collapse", &[star]) => Action::DestroyStar { star },
+            ("capture", &[ship, star]) => Action::CaptureShip { ship, star },
+            _ => return Err(()),
+        };
+        Ok(action)
+    }
+}
+
+/// `resign`, `offer`, `accept` or `decline`
+impl std::fmt::Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let word = match self{
+            Decision::Resign => "resign",
+            Decision::OfferDraw => "offer",
+            Decision::AcceptDraw => "accept",
+            Decision::DeclineDraw => "decline",
+        };
+        write!(f, "{word}")
+    }
+}
+
+impl std::str::FromStr for Decision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim(){
+            "resign" => Ok(Decision::Resign),
+            "offer" => Ok(Decision::OfferDraw),
+            "accept" => Ok(Decision::AcceptDraw),
+            "decline" => Ok(Decision::DeclineDraw),
+            _ => Err(()),
+        }
+    }
+}
+
+/// a turn as one line, its actions separated by `; ` or `pass` if there are none
+pub(crate) fn format_turn(turn: &[Action])->String{
+    if turn.is_empty() {
+        return "pass".to_string();
+    }
+    let actions: Vec<String> = turn.iter().map(|action|action.to_string()).collect();
+    actions.join("; ")
+}
+
+/// reads a line written by `format_turn`
+pub(crate) fn parse_turn(line: &str)->Option<Vec<Action>>{
+    match line.trim(){
+        "pass" => Some(Vec::new()),
+        line => line.split(';').map(|action|action.parse().ok()).collect(),
+    }
+}
+
+/// a game as it is saved: who played, how it ended and every turn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Record{
+    pub(crate) player_count: u8,
+    pub(crate) set: PieceSet,
+    /// none while the game is still going, `Some(None)` for a draw
+    pub(crate) result: Option<Option<u8>>,
+    pub(crate) turns: Vec<Vec<Action>>,
+    /// resignations and draw offers, in the order they were made
+    pub(crate) events: Vec<Event>,
+}
+
+impl Record{
+    /// `players 2`, `pieces rgy/123/2` unless the game is played with the
+    /// standard set, an optional `result 1` or `result draw`, then one line
+    /// per turn with its actions separated by `;`, or `pass`. Decisions like
+    /// `decision 1 offer` go between the turns they were made between. Lines
+    /// starting with `#` are comments.
+    pub(crate) fn parse(text: &str)->Option<Record>{
+        let mut lines = text.lines().map(|line|line.trim()).filter(|line|!line.is_empty() && !line.starts_with('#')).peekable();
+        let player_count = lines.next()?.strip_prefix("players ")?.parse().ok().filter(|count|(2..=4).contains(count))?;
+        let set = match lines.peek().and_then(|line|line.strip_prefix("pieces ")){
+            Some(set) => set.parse().ok()?,
+            None => PieceSet::STANDARD,
+        };
+        if set != PieceSet::STANDARD {
+            lines.next();
+        }
+        let result = match lines.peek().and_then(|line|line.strip_prefix("result ")){
+            Some("draw") => Some(None),
+            Some(winner) => Some(Some(winner.parse().ok()?)),
+            None => None,
+        };
+        if result.is_some() {
+            lines.next();
+        }
+        let mut turns = Vec::new();
+        let mut events = Vec::new();
+        for line in lines{
+            match line.strip_prefix("decision "){
+                Some(decision) => {
+                    let (player, decision) = decision.trim().split_once(' ')?;
+                    let (player, decision) = (player.parse().ok()?, decision.parse().ok()?);
+                    events.push(Event { turn: turns.len(), player, decision });
+                },
+                None => turns.push(parse_turn(line)?),
+            }
+        }
+        Some(Record { player_count, set, result, turns, events })
+    }
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "players {}", self.player_count)?;
+        if self.set != PieceSet::STANDARD {
+            writeln!(f, "pieces {}", self.set)?;
+        }
+        match self.result{
+            Some(Some(winner)) => writeln!(f, "result {winner}")?,
+            Some(None) => writeln!(f, "result draw")?,
+            None => {},
+        }
+        let mut events = self.events.iter().peekable();
+        for (i, turn) in self.turns.iter().enumerate(){
+            while let Some(event) = events.next_if(|event|event.turn <= i) {
+                writeln!(f, "decision {} {}", event.player, event.decision)?;
+            }
+            writeln!(f, "{}", format_turn(turn))?;
+        }
+        for event in events{
+            writeln!(f, "decision {} {}", event.player, event.decision)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::piece;
+
+    #[test]
+    fn actions_round_trip(){
+        use {Color::*, Size::*};
+        let actions = [
+            Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) },
+            Action::MoveShip { ship: piece(Green, Large), start_star: piece(Red, Small), end_star: piece(Yellow, Large) },
+            Action::DestroyStar { star: Id(ID_COUNT as u8 - 1) },
+            Action::Catastrophe { star: piece(Red, Small), color: Green },
+            Action::CaptureShip { ship: piece(Blue, Large), star: piece(Red, Small) },
+        ];
+        assert_eq!(actions[0].to_string(), "homeworld r1a b2a g3a");
+        assert_eq!(actions[2].to_string(), "collapse y4c");
+        assert_eq!(actions[3].to_string(), "catastrophe green r1a");
+        for action in actions{
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+        for bad in ["", "fly g3a", "build g3a", "build g5a r1a", "build g0a r1a", "build g3d r1a", "build g3aa r1a", "catastrophe r1a", "catastrophe pink r1a"]{
+            assert_eq!(bad.parse::<Action>(), Err(()), "{bad}");
+        }
+    }
+
+    #[test]
+    fn records_round_trip(){
+        use {Color::*, Size::*};
+        let mut record = Record {
+            player_count: 2,
+            set: PieceSet::STANDARD,
+            result: Some(None),
+            turns: vec![
+                vec![Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) }],
+                vec![],
+                vec![
+                    Action::CreateShip { ship: piece(Green, Small), star: piece(Red, Small) },
+                    Action::DestroyShip { ship: piece(Green, Small), star: piece(Red, Small) },
+                ],
+            ],
+            events: vec![
+                Event { turn: 1, player: 1, decision: Decision::OfferDraw },
+                Event { turn: 3, player: 0, decision: Decision::AcceptDraw },
+            ],
+        };
+        let text = record.to_string();
+        assert_eq!(text, "players 2\nresult draw\nhomeworld r1a b2a g3a\ndecision 1 offer\npass\nbuild g1a r1a; destroy g1a r1a\ndecision 0 accept\n");
+        assert_eq!(Record::parse(&text), Some(record.clone()));
+        assert_eq!(Record::parse("players 3\nresult 2\n").unwrap().result, Some(Some(2)));
+        assert_eq!(Record::parse("result draw\n"), None);
+        // `Position` has room for four players
+        assert_eq!(Record::parse("players 1\n"), None);
+        assert_eq!(Record::parse("players 5\n"), None);
+
+        record.set = "rgy/123/2".parse().unwrap();
+        let text = record.to_string();
+        assert!(text.starts_with("players 2\npieces rgy/123/2\nresult draw\n"));
+        assert_eq!(Record::parse(&text), Some(record));
+        assert_eq!(Record::parse("players 2\npieces rgy/123/5\n"), None);
+    }
+}
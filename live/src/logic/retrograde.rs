@@ -0,0 +1,475 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::*;
+use super::ai::{Engine, Limits, DECISIVE};
+
+/// a solved position, for the player to move. Distances count turns until the
+/// game ends, this one included: `Win(1)` wins with this turn, `Loss(2)` loses
+/// to the next one whatever is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Value{
+    Win(u16),
+    Loss(u16),
+    Draw,
+}
+
+impl Value{
+    /// higher is better, winning sooner and losing later
+    fn rank(self)->i32{
+        match self{
+            Value::Win(turns) => i32::MAX - turns as i32,
+            Value::Draw => 0,
+            Value::Loss(turns) => i32::MIN + turns as i32,
+        }
+    }
+
+    /// the value for the player who moved into a position with this value
+    pub(super) fn previous(self)->Value{
+        match self{
+            Value::Win(turns) => Value::Loss(turns + 1),
+            Value::Loss(turns) => Value::Win(turns + 1),
+            Value::Draw => Value::Draw,
+        }
+    }
+
+    /// a search score, decisive results are worth the same as in `Engine`
+    pub(crate) fn score(self)->f32{
+        match self{
+            Value::Win(turns) => DECISIVE - turns as f32,
+            Value::Draw => 0.0,
+            Value::Loss(turns) => turns as f32 - DECISIVE,
+        }
+    }
+
+    /// how the value is stored, positive for wins and negative for losses
+    fn to_i16(self)->i16{
+        match self{
+            Value::Win(turns) => turns as i16,
+            Value::Draw => 0,
+            Value::Loss(turns) => -(turns as i16),
+        }
+    }
+
+    fn from_i16(value: i16)->Value{
+        match value{
+            0 => Value::Draw,
+            1.. => Value::Win(value as u16),
+            _ => Value::Loss(value.unsigned_abs()),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Value::Win(turns) => write!(f, "win in {turns}"),
+            Value::Loss(turns) => write!(f, "loss in {turns}"),
+            Value::Draw => write!(f, "draw"),
+        }
+    }
+}
+
+/// how a turn from `before` to `after` ends the game for the player who took it,
+/// none if it goes on. Players who never had a homeworld are still setting up.
+fn ends(before: &Board, after: &Board)->Option<Value>{
+    let out = |player: u8|{
+        let had_home = before.homeworld(player).is_some() || after.homeworld(player).is_some();
+        had_home && after.homeworld(player)
+            .and_then(|id|after.system(id))
+            .is_none_or(|home|home.ships[player as usize].is_empty())
+    };
+    let player = before.current_player;
+    match (out(player), out(1 - player)){
+        (false, false) => None,
+        (false, true) => Some(Value::Win(1)),
+        (true, false) => Some(Value::Loss(1)),
+        (true, true) => Some(Value::Draw),
+    }
+}
+
+/// a homeworld, `NO_HOME` in a key while there is none
+const NO_HOME: u8 = u8::MAX;
+
+/// the position as bytes, the same for boards that differ only in which copy
+/// of a kind is where: the player to move, both homeworlds with their stars
+/// and each player's ships, then the other systems sorted
+fn key(board: &Board)->Vec<u8>{
+    let kinds = |ids: &[Id]|{
+        let mut kinds: Vec<u8> = ids.iter().map(|id|id.kind() as u8).collect();
+        kinds.sort();
+        kinds
+    };
+    let fleets = |key: &mut Vec<u8>, system: &System|{
+        for ships in system.ships.iter(){
+            let ships = kinds(ships);
+            key.push(ships.len() as u8);
+            key.extend(ships);
+        }
+    };
+    let mut key = vec![board.current_player];
+    for player in 0..board.player_count{
+        match board.homeworld(player).and_then(|id|board.system(id)){
+            Some(home) => {
+                let stars = kinds(&home.stars);
+                key.push(stars.len() as u8);
+                key.extend(stars);
+                fleets(&mut key, home);
+            },
+            None => key.push(NO_HOME),
+        }
+    }
+    let mut others: Vec<Vec<u8>> = board.systems().filter(|(_, system)|system.home.is_none()).map(|(_, system)|{
+        let mut key = vec![system.stars[0].kind() as u8];
+        fleets(&mut key, system);
+        key
+    }).collect();
+    others.sort();
+    key.extend(others.concat());
+    key
+}
+
+/// a board of `set` for `key`, each piece the lowest copy of its kind still free
+fn board_from_key(set: PieceSet, key: &[u8])->Option<Board>{
+    let mut board = Board::with_set(2, set);
+    let mut bytes = key.iter().copied();
+    board.current_player = bytes.next().filter(|player|*player < 2)?;
+    let mut take = |kind: u8|{
+        let id = board.bank.iter().copied().filter(|id|id.kind() == kind as usize).min()?;
+        board.bank.remove(&id);
+        Some(id)
+    };
+    let fleets = |bytes: &mut dyn Iterator<Item = u8>, take: &mut dyn FnMut(u8)->Option<Id>|->Option<Box<[Vec<Id>]>>{
+        (0..2).map(|_|{
+            let count = bytes.next()?;
+            (0..count).map(|_|take(bytes.next()?)).collect::<Option<Vec<Id>>>()
+        }).collect()
+    };
+    let mut systems = Vec::new();
+    for player in 0..2{
+        let count = bytes.next()?;
+        if count == NO_HOME {
+            continue;
+        }
+        let stars = (0..count).map(|_|take(bytes.next()?)).collect::<Option<Vec<Id>>>()?;
+        let ships = fleets(&mut bytes, &mut take)?;
+        systems.push(System { stars, home: Some(player), ships });
+    }
+    let mut bytes = bytes.peekable();
+    while bytes.peek().is_some(){
+        let star = take(bytes.next()?)?;
+        let ships = fleets(&mut bytes, &mut take)?;
+        systems.push(System { stars: vec![star], home: None, ships });
+    }
+    for system in systems{
+        board.insert_system(system);
+    }
+    board.validate().ok()?;
+    Some(board)
+}
+
+/// how the engine's turns compare to the best ones, see `Tablebase::verify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Verification{
+    pub(crate) positions: usize,
+    /// the engine's turn is worth as much as the best one
+    pub(crate) best: usize,
+    /// the result stays the same but takes longer to win or comes sooner in a loss
+    pub(crate) slower: usize,
+    /// a win or draw thrown away
+    pub(crate) worse: usize,
+}
+
+/// win, loss and draw distances for every position of a two player game with
+/// a reduced piece set, from the empty board on. Solved by retrograde
+/// analysis: positions that end the game are known, the rest is worked out
+/// backwards from them. Repetitions and quiet turns are not counted, so a
+/// draw here is a game neither side can force to an end.
+pub(crate) struct Tablebase{
+    set: PieceSet,
+    values: HashMap<Vec<u8>, Value>,
+}
+
+impl std::fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tablebase").field("set", &self.set).field("positions", &self.values.len()).finish()
+    }
+}
+
+/// the first bytes of a tablebase file, with the version of the key layout
+const MAGIC: &[u8] = b"hwtb 2\n";
+
+impl Tablebase{
+    /// solves `set`, giving up once there are more than `max_positions`
+    pub(crate) fn solve(set: PieceSet, max_positions: usize)->Result<Tablebase, String>{
+        // every position reachable from the empty board, with its successors
+        let start = Board::with_set(2, set);
+        let mut index: HashMap<Vec<u8>, u32> = HashMap::from([(key(&start), 0)]);
+        let mut queue = VecDeque::from([start]);
+        let mut successors: Vec<Vec<u32>> = Vec::new();
+        // the best a turn that ends the game at once gets
+        let mut ending: Vec<Option<Value>> = Vec::new();
+        while let Some(board) = queue.pop_front(){
+            let mut next = Vec::new();
+            let mut best: Option<Value> = None;
+            for (_, mut after) in board.legal_turns(){
+                if let Some(value) = ends(&board, &after) {
+                    best = best.into_iter().chain([value]).max_by_key(|value|value.rank());
+                    continue;
+                }
+                after.end_turn();
+                let count = index.len() as u32;
+                let i = *index.entry(key(&after)).or_insert_with(||{
+                    queue.push_back(after);
+                    count
+                });
+                next.push(i);
+            }
+            if index.len() > max_positions {
+                return Err(format!("{set} has more than {max_positions} positions"));
+            }
+            next.sort();
+            next.dedup();
+            successors.push(next);
+            ending.push(best);
+        }
+
+        let count = successors.len();
+        let mut predecessors = vec![Vec::new(); count];
+        for (i, next) in successors.iter().enumerate(){
+            for j in next{
+                predecessors[*j as usize].push(i as u32);
+            }
+        }
+        // successors not known to win for the opponent yet, a drawing turn never is
+        let mut open: Vec<usize> = successors.iter().zip(&ending)
+            .map(|(next, ending)|next.len() + (*ending == Some(Value::Draw)) as usize)
+            .collect();
+        // known from the start: a win on the spot, or a loss when every turn loses at once
+        let mut values: Vec<Option<Value>> = successors.iter().zip(&ending)
+            .map(|(next, ending)|match ending{
+                Some(Value::Win(1)) => Some(Value::Win(1)),
+                Some(Value::Loss(1)) if next.is_empty() => Some(Value::Loss(1)),
+                _ => None,
+            })
+            .collect();
+
+        // one layer per distance, so the first win found is the fastest and
+        // the last successor to fall makes the slowest loss
+        let mut layer: Vec<usize> = (0..count).filter(|i|values[*i].is_some()).collect();
+        while !layer.is_empty(){
+            let mut next = Vec::new();
+            for j in layer{
+                let value = values[j].expect("layers only hold solved positions");
+                for i in &predecessors[j]{
+                    let i = *i as usize;
+                    if values[i].is_some() {
+                        continue;
+                    }
+                    match value{
+                        Value::Loss(_) => {
+                            values[i] = Some(value.previous());
+                            next.push(i);
+                        },
+                        Value::Win(_) => {
+                            open[i] -= 1;
+                            if open[i] == 0 {
+                                values[i] = Some(value.previous());
+                                next.push(i);
+                            }
+                        },
+                        Value::Draw => {},
+                    }
+                }
+            }
+            layer = next;
+        }
+
+        let values = index.into_iter()
+            .map(|(key, i)|(key, values[i as usize].unwrap_or(Value::Draw)))
+            .collect();
+        Ok(Tablebase { set, values })
+    }
+
+    pub(crate) fn set(&self)->PieceSet{
+        self.set
+    }
+
+    /// positions per value: wins, losses and draws for the player to move
+    pub(crate) fn counts(&self)->(usize, usize, usize){
+        let mut counts = (0, 0, 0);
+        for value in self.values.values(){
+            match value{
+                Value::Win(_) => counts.0 += 1,
+                Value::Loss(_) => counts.1 += 1,
+                Value::Draw => counts.2 += 1,
+            }
+        }
+        counts
+    }
+
+    /// the value of `board` for the player to move, none if it isn't a
+    /// position of this piece set
+    pub(crate) fn probe(&self, board: &Board)->Option<Value>{
+        if board.player_count != 2 || board.set != self.set {
+            return None;
+        }
+        self.values.get(&key(board)).copied()
+    }
+
+    /// the value of the turn from `board` to `after` for the player who takes it
+    fn turn_value(&self, board: &Board, after: &Board)->Option<Value>{
+        if let Some(value) = ends(board, after) {
+            return Some(value);
+        }
+        let mut next = after.clone();
+        next.end_turn();
+        Some(self.probe(&next)?.previous())
+    }
+
+    /// the best turn on `board` and what it is worth, none outside this piece set
+    pub(crate) fn best_turn(&self, board: &Board)->Option<(Vec<Action>, Value)>{
+        self.probe(board)?;
+        board.legal_turns().into_iter()
+            .filter_map(|(turn, after)|Some((turn, self.turn_value(board, &after)?)))
+            .max_by_key(|(_, value)|value.rank())
+    }
+
+    /// compares the turns `engine` picks in up to `sample` positions, spread
+    /// evenly over the table, to the best ones
+    pub(crate) fn verify(&self, engine: &mut Engine, limits: Limits, sample: usize)->Verification{
+        let mut keys: Vec<&Vec<u8>> = self.values.keys().collect();
+        keys.sort();
+        let step = keys.len().div_ceil(sample.max(1)).max(1);
+        let mut verification = Verification::default();
+        for key in keys.into_iter().step_by(step){
+            let Some(board) = board_from_key(self.set, key) else {
+                continue;
+            };
+            let Some((_, best)) = self.best_turn(&board) else {
+                continue;
+            };
+            let turn = engine.search(&board, None, limits).turn;
+            let after = turn.iter().try_fold(board.clone(), |board, action|board.apply_action(*action));
+            let Some(value) = after.and_then(|after|self.turn_value(&board, &after)) else {
+                continue;
+            };
+            verification.positions += 1;
+            let same_result = std::mem::discriminant(&value) == std::mem::discriminant(&best);
+            if value == best {
+                verification.best += 1;
+            }else if same_result {
+                verification.slower += 1;
+            }else{
+                verification.worse += 1;
+            }
+        }
+        verification
+    }
+
+    /// the table as bytes: a header, the piece set on a line, the number of
+    /// positions, then each position's key length, key and value
+    pub(crate) fn to_bytes(&self)->Vec<u8>{
+        let mut entries: Vec<(&Vec<u8>, &Value)> = self.values.iter().collect();
+        entries.sort_by_key(|(key, _)|*key);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(format!("{}\n", self.set).as_bytes());
+        bytes.extend((entries.len() as u32).to_le_bytes());
+        for (key, value) in entries{
+            bytes.push(key.len() as u8);
+            bytes.extend(key);
+            bytes.extend(value.to_i16().to_le_bytes());
+        }
+        bytes
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8])->Result<Tablebase, String>{
+        let rest = bytes.strip_prefix(MAGIC).ok_or("not a tablebase")?;
+        let end = rest.iter().position(|byte|*byte == b'\n').ok_or("no piece set")?;
+        let set: PieceSet = std::str::from_utf8(&rest[..end]).map_err(|_|"no piece set")?.parse()?;
+        let mut rest = &rest[end + 1..];
+        let mut read = |count: usize|->Result<&[u8], String>{
+            let (read, after) = rest.split_at_checked(count).ok_or("the file is cut short")?;
+            rest = after;
+            Ok(read)
+        };
+        let count = u32::from_le_bytes(read(4)?.try_into().expect("4 bytes"));
+        let mut values = HashMap::new();
+        for _ in 0..count{
+            let length = read(1)?[0] as usize;
+            let key = read(length)?.to_vec();
+            let value = i16::from_le_bytes(read(2)?.try_into().expect("2 bytes"));
+            values.insert(key, Value::from_i16(value));
+        }
+        Ok(Tablebase { set, values })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::eval::Weights;
+
+    fn tiny()->PieceSet{
+        "rg/13/2".parse().unwrap()
+    }
+
+    #[test]
+    fn keys_round_trip(){
+        let set: PieceSet = "rgy/123/1".parse().unwrap();
+        let mut board = Board::with_set(2, set);
+        for _ in 0..6{
+            let Some((_, after)) = board.legal_turns().pop() else {
+                break;
+            };
+            board = after;
+            board.end_turn();
+            let decoded = board_from_key(set, &key(&board)).unwrap();
+            assert_eq!(key(&decoded), key(&board));
+        }
+    }
+
+    #[test]
+    fn solves_a_tiny_set(){
+        let table = Tablebase::solve(tiny(), 100_000).unwrap();
+        let (wins, losses, draws) = table.counts();
+        // small sets always leave a drawing turn, so nobody is forced to lose
+        assert!(wins > 0 && draws > 0);
+        assert_eq!(losses, 0);
+        assert_eq!(wins + losses + draws, table.values.len());
+        assert!(Tablebase::solve(tiny(), 10).is_err());
+
+        // every value agrees with the best turn from it
+        for key in table.values.keys(){
+            let board = board_from_key(table.set, key).unwrap();
+            let (turn, value) = table.best_turn(&board).unwrap();
+            assert_eq!(Some(value), table.probe(&board), "{board}: {turn:?}");
+        }
+        // and no turn from a lost position gets out of it
+        for key in table.values.keys().filter(|key|matches!(table.values[*key], Value::Loss(_))){
+            let board = board_from_key(table.set, key).unwrap();
+            for (_, after) in board.legal_turns(){
+                assert!(matches!(table.turn_value(&board, &after), Some(Value::Loss(_))));
+            }
+        }
+        // boards of other piece sets aren't in the table
+        assert_eq!(table.probe(&Board::new(2)), None);
+
+        let read = Tablebase::from_bytes(&table.to_bytes()).unwrap();
+        assert_eq!(read.set, table.set);
+        assert_eq!(read.values, table.values);
+        assert!(Tablebase::from_bytes(&table.to_bytes()[..40]).is_err());
+
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        let verification = table.verify(&mut engine, Limits::default(), 20);
+        assert!(verification.positions > 0);
+        assert_eq!(verification.positions, verification.best + verification.slower + verification.worse);
+
+        // an engine that probes the table always plays the best turn
+        engine.tablebase = Some(std::sync::Arc::new(read));
+        let verification = table.verify(&mut engine, Limits::default(), 20);
+        assert_eq!(verification.best, verification.positions);
+        let search = engine.search(&Board::with_set(2, table.set), None, Limits::default());
+        assert!(search.solved);
+        assert_eq!(search.score, Value::Draw.score());
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+
+use super::*;
+use super::position::Position;
+
+/// a system's owner, the kinds of its stars and those of each player's ships
+type SystemKinds = (Option<u8>, Vec<usize>, Vec<Vec<usize>>);
+
+impl Board{
+    /// `player`'s ships at the system, one of every kind
+    fn ship_kinds(system: &System, player: u8)->Vec<Id>{
+        let mut ships = system.ships[player as usize].clone();
+        ships.sort();
+        ships.dedup_by_key(|ship|ship.kind());
+        ships
+    }
+
+    /// the lowest bank id of every kind that passes `filter`
+    fn bank_kinds(&self, filter: impl Fn(Id)->bool)->Vec<Id>{
+        let mut ids: Vec<Id> = self.bank.iter().copied().filter(|id|filter(*id)).collect();
+        ids.sort();
+        ids.dedup_by_key(|id|id.kind());
+        ids
+    }
+
+    /// the ways the current player can use `color` once at `system`, each
+    /// a short list of actions. Trading builds the new ship before the old
+    /// one leaves, so the system is never empty in between.
+    pub(super) fn color_actions(&self, system: SystemId, color: Color)->Vec<Vec<Action>>{
+        let player = self.current_player;
+        let Some(here) = self.system(system) else {
+            return Vec::new();
+        };
+        let star = here.stars[0];
+        let ours = Self::ship_kinds(here, player);
+        let mut actions = Vec::new();
+        match color{
+            Color::Red => {
+                let Some(biggest) = ours.iter().map(|ship|ship.size()).max() else {
+                    return actions;
+                };
+                for owner in (0..self.player_count).filter(|owner|*owner != player){
+                    for ship in Self::ship_kinds(here, owner).into_iter().filter(|ship|ship.size() <= biggest){
+                        actions.push(vec![Action::CaptureShip { ship, star }]);
+                    }
+                }
+            },
+            Color::Green => {
+                let mut colors: Vec<Color> = ours.iter().map(|ship|ship.color()).collect();
+                colors.dedup();
+                for ship_color in colors{
+                    // always the smallest size left in the bank
+                    if let Some(ship) = self.bank_kinds(|id|id.color() == ship_color).first() {
+                        actions.push(vec![Action::CreateShip { ship: *ship, star }]);
+                    }
+                }
+            },
+            Color::Blue => {
+                for ship in &ours{
+                    for new_ship in self.bank_kinds(|id|id.size() == ship.size() && id.color() != ship.color()){
+                        actions.push(vec![
+                            Action::CreateShip { ship: new_ship, star },
+                            Action::DestroyShip { ship: *ship, star },
+                        ]);
+                    }
+                }
+            },
+            Color::Yellow => {
+                let sizes: Vec<Size> = here.stars.iter().map(|star|star.size()).collect();
+                let discoveries = self.bank_kinds(|id|!sizes.contains(&id.size()));
+                for ship in &ours{
+                    for (other, other_system) in self.systems(){
+                        if other != system && here.connected(other_system) {
+                            actions.push(vec![Action::MoveShip { ship: *ship, start_star: star, end_star: other_system.stars[0] }]);
+                        }
+                    }
+                    for new_star in &discoveries{
+                        actions.push(vec![Action::CreateStar { star: *new_star, ship: *ship, start_star: star }]);
+                    }
+                }
+            },
+        }
+        actions
+    }
+
+    /// every catastrophe that could be triggered right now
+    pub(super) fn catastrophes(&self)->Vec<Action>{
+        self.systems()
+            .flat_map(|(_, system)|COLORS.into_iter()
+                .filter(|color|system.color_count(*color) >= 4)
+                .map(|color|Action::Catastrophe { star: system.stars[0], color }))
+            .collect()
+    }
+
+    /// every distinct turn the current player can take, with the board it leaves.
+    /// A turn is a pass, one action with a color available where the ship is, or
+    /// a sacrifice followed by up to as many actions of its color as its size.
+    /// Catastrophes are offered at the end of a turn, either all of them or none.
+    /// Turns that lead to the same position are only listed once.
+    pub(crate) fn legal_turns(&self)->Vec<(Vec<Action>, Board)>{
+        let player = self.current_player;
+        if self.homeworld(player).is_none() {
+            return self.homeworld_advice(player).into_iter()
+                .map(|advice|vec![Action::CreateHomeworld { stars: advice.stars, ship: advice.ship }])
+                .filter_map(|turn|Some((turn.clone(), self.clone().apply_action(turn[0])?)))
+                .collect();
+        }
+
+        let mut turns = vec![(Vec::new(), self.clone())];
+        for (id, system) in self.systems().filter(|(_, system)|!system.ships[player as usize].is_empty()){
+            for color in system.powers(player).iter(){
+                for actions in self.color_actions(id, color){
+                    turns.extend(self.play(&[], &actions));
+                }
+            }
+        }
+
+        let mut sacrifices: Vec<(Id, Id)> = self.systems()
+            .flat_map(|(_, system)|Self::ship_kinds(system, player).into_iter().map(|ship|(ship, system.stars[0])))
+            .collect();
+        sacrifices.dedup();
+        for (ship, star) in sacrifices{
+            let Some((start, board)) = self.play(&[], &[Action::DestroyShip { ship, star }]) else {
+                continue;
+            };
+            // breadth first over what is left of the sacrifice
+            let mut layer = vec![(start, board)];
+            for _ in 0..ship.size() as usize + 1{
+                let mut seen = HashSet::new();
+                let mut next = Vec::new();
+                for (actions, board) in &layer{
+                    let systems: Vec<SystemId> = board.systems()
+                        .filter(|(_, system)|!system.ships[player as usize].is_empty())
+                        .map(|(id, _)|id)
+                        .collect();
+                    for system in systems{
+                        for more in board.color_actions(system, ship.color()){
+                            if let Some((actions, board)) = board.play(actions, &more) {
+                                if seen.insert(Position::from(&board)) {
+                                    next.push((actions, board));
+                                }
+                            }
+                        }
+                    }
+                }
+                turns.append(&mut layer);
+                layer = next;
+            }
+            turns.append(&mut layer);
+        }
+
+        let with_catastrophes: Vec<_> = turns.iter().filter_map(|(actions, board)|{
+            let catastrophes = board.catastrophes();
+            if catastrophes.is_empty() {
+                return None;
+            }
+            // one catastrophe can end another, so skip those that no longer apply
+            let mut actions = actions.clone();
+            let mut board = board.clone();
+            for catastrophe in catastrophes{
+                if let Some(next) = board.clone().apply_action(catastrophe) {
+                    board = next;
+                    actions.push(catastrophe);
+                }
+            }
+            Some((actions, board))
+        }).collect();
+        turns.extend(with_catastrophes);
+
+        let mut seen = HashSet::new();
+        turns.retain(|(_, board)|seen.insert(Position::from(board)));
+        turns
+    }
+
+    /// the board after `turn`, none unless it is one of `legal_turns`. Every
+    /// action can be fine on its own while the turn as a whole breaks the
+    /// rules. Turns that lead where a listed one does count as it, whichever
+    /// copies of a piece they use.
+    pub(crate) fn legal_turn(&self, turn: &[Action])->Option<Board>{
+        let after = turn.iter().try_fold(self.clone(), |board, action|board.apply_action(*action))?;
+        let layout = after.layout();
+        self.legal_turns().iter().any(|(_, board)|board.layout() == layout).then_some(after)
+    }
+
+    /// every system as its owner and the kinds of its stars and of each
+    /// player's ships, sorted. `Position` tells stars apart by copy, this doesn't.
+    pub(super) fn layout(&self)->Vec<SystemKinds>{
+        let kinds = |ids: &[Id]|{
+            let mut kinds: Vec<usize> = ids.iter().map(|id|id.kind()).collect();
+            kinds.sort();
+            kinds
+        };
+        let mut layout: Vec<_> = self.systems()
+            .map(|(_, system)|(system.home, kinds(&system.stars), system.ships.iter().map(|ships|kinds(ships)).collect()))
+            .collect();
+        layout.sort();
+        layout
+    }
+
+    /// `actions` applied after `played`, none if one is rejected
+    fn play(&self, played: &[Action], actions: &[Action])->Option<(Vec<Action>, Board)>{
+        let mut board = self.clone();
+        for action in actions{
+            board = board.apply_action(*action)?;
+        }
+        Some((played.iter().chain(actions).copied().collect(), board))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn turns_from_a_small_position(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Yellow, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Blue, Large)], None, [vec![piece(Blue, Small)], vec![piece(Red, Medium)]]),
+            system(vec![piece(Yellow, Small), piece(Green, Medium)], Some(1), [vec![], vec![piece(Blue, Medium)]]),
+        ]);
+        let turns = board.legal_turns();
+        let has = |turn: &[Action]|turns.iter().any(|(actions, _)|actions == turn);
+        let blue_large = piece(Blue, Large);
+        let red_small = Id::new(Red, Small, 1);
+
+        assert!(has(&[]));
+        // no red at the blue star, the one at home doesn't count there
+        assert!(!turns.iter().any(|(actions, _)|matches!(actions.as_slice(), [Action::CaptureShip { .. }])));
+        // the blue star lets the small blue trade for a small red
+        assert!(has(&[
+            Action::CreateShip { ship: red_small, star: blue_large },
+            Action::DestroyShip { ship: piece(Blue, Small), star: blue_large },
+        ]));
+        // the green large builds the smallest green left
+        assert!(has(&[Action::CreateShip { ship: piece(Green, Small), star: piece(Red, Small) }]));
+        // yellow at home: to the large blue, which has no size in common
+        assert!(has(&[Action::MoveShip { ship: piece(Green, Large), start_star: piece(Red, Small), end_star: blue_large }]));
+        // sacrificing the small blue and not using the trade it gives
+        assert!(has(&[Action::DestroyShip { ship: piece(Blue, Small), star: blue_large }]));
+
+        let mut seen = HashSet::new();
+        for (actions, after) in &turns{
+            assert!(seen.insert(Position::from(after)), "{actions:?} repeats a position");
+            let replayed = actions.iter().try_fold(board.clone(), |board, action|board.apply_action(*action));
+            assert_eq!(replayed.as_ref(), Some(after));
+            assert_eq!(board.legal_turn(actions).as_ref(), Some(after));
+        }
+        // each build is fine on its own, two of them without a sacrifice aren't
+        let builds = [
+            Action::CreateShip { ship: piece(Green, Small), star: piece(Red, Small) },
+            Action::CreateShip { ship: Id::new(Green, Small, 1), star: piece(Red, Small) },
+        ];
+        assert!(builds.iter().try_fold(board.clone(), |board, action|board.apply_action(*action)).is_some());
+        assert_eq!(board.legal_turn(&builds), None);
+        // any copies, in any order
+        let home = Action::CreateHomeworld { stars: [Id::new(Yellow, Small, 2), Id::new(Blue, Large, 1)], ship: Id::new(Green, Medium, 1) };
+        assert!(Board::new(2).legal_turn(&[home]).is_some());
+    }
+
+    #[test]
+    fn sacrifices_and_catastrophes(){
+        use {Color::*, Size::*};
+        let yellow = [0, 1, 2].map(|copy|Id::new(Yellow, Medium, copy));
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![yellow[0], piece(Green, Large)], vec![]]),
+            system(vec![piece(Yellow, Large)], None, [vec![yellow[1]], vec![yellow[2]]]),
+            system(vec![piece(Green, Small), piece(Blue, Large)], Some(1), [vec![], vec![piece(Red, Large)]]),
+        ]);
+        let turns = board.legal_turns();
+        let has = |turn: &[Action]|turns.iter().any(|(actions, _)|actions == turn);
+
+        // a medium yellow sacrifice moves twice
+        let sacrifice = Action::DestroyShip { ship: yellow[0], star: piece(Red, Small) };
+        let home = board.homeworld(0).unwrap();
+        assert!(turns.iter().any(|(actions, after)|{
+            actions.len() == 3 && actions[0] == sacrifice && after.system(home).unwrap().ships[0].is_empty()
+        }));
+        // moving the other medium yellow in makes four yellow pieces there
+        let move_in = Action::MoveShip { ship: yellow[0], start_star: piece(Red, Small), end_star: piece(Yellow, Large) };
+        assert!(has(&[move_in]));
+        let catastrophe = Action::Catastrophe { star: piece(Yellow, Large), color: Yellow };
+        assert!(has(&[move_in, catastrophe]));
+    }
+}
@@ -0,0 +1,170 @@
+use super::*;
+
+/// a rated homeworld choice, higher scores are better
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HomeworldAdvice{
+    pub(crate) stars: [Id; 2],
+    pub(crate) ship: Id,
+    pub(crate) score: i32,
+    /// why the choice lost points, worst first
+    pub(crate) risks: Vec<String>,
+}
+
+/// fewest moves between two homeworlds with these star sizes, assuming the
+/// bank can provide any star in between of the `sizes` the game is played with
+fn home_distance(a: &[Size], b: &[Size], sizes: &[Size])->u8{
+    if a.iter().all(|size|!b.contains(size)) {
+        1
+    }else if sizes.iter().any(|size|!a.contains(size) && !b.contains(size)) {
+        2
+    }else{
+        3
+    }
+}
+
+impl Board{
+    /// the ids of `count` different bank pieces of `kind`
+    fn bank_pieces(&self, kind: usize, count: usize)->Option<Vec<Id>>{
+        let mut ids: Vec<Id> = self.bank.iter().copied().filter(|id|id.kind() == kind).collect();
+        ids.sort();
+        ids.truncate(count);
+        (ids.len() == count).then_some(ids)
+    }
+
+    /// every homeworld `player` could pick from the bank, best first.
+    /// Pieces of the same color and size are only listed once.
+    pub(crate) fn homeworld_advice(&self, player: u8)->Vec<HomeworldAdvice>{
+        let opponent_homes: Vec<(u8, Vec<Size>)> = (0..self.player_count)
+            .filter(|other|*other != player)
+            .filter_map(|other|{
+                let home = self.system(self.homeworld(other)?)?;
+                Some((other, home.stars.iter().map(|star|star.size()).collect()))
+            })
+            .collect();
+
+        let sizes: Vec<Size> = self.set.sizes().collect();
+        let kinds: Vec<usize> = self.set.kinds().collect();
+        let mut advice = Vec::new();
+        for (i, &a) in kinds.iter().enumerate(){
+            for &b in &kinds[i..]{
+                for &ship in &kinds{
+                    let mut wanted = [0; KIND_COUNT];
+                    for kind in [a, b, ship]{
+                        wanted[kind] += 1;
+                    }
+                    let mut pieces = Vec::new();
+                    for (kind, count) in wanted.iter().enumerate().filter(|(_, count)|**count > 0){
+                        match self.bank_pieces(kind, *count){
+                            Some(ids) => pieces.extend(ids),
+                            None => break,
+                        }
+                    }
+                    if pieces.len() < 3 {
+                        continue;
+                    }
+                    let take = |kind: usize, pieces: &mut Vec<Id>|{
+                        let i = pieces.iter().position(|id|id.kind() == kind).unwrap();
+                        pieces.remove(i)
+                    };
+                    let stars = [take(a, &mut pieces), take(b, &mut pieces)];
+                    let ship = take(ship, &mut pieces);
+                    advice.push(rate(stars, ship, &opponent_homes, &sizes));
+                }
+            }
+        }
+        advice.sort_by_key(|advice|-advice.score);
+        advice
+    }
+}
+
+fn rate(stars: [Id; 2], ship: Id, opponent_homes: &[(u8, Vec<Size>)], game_sizes: &[Size])->HomeworldAdvice{
+    let mut score = 0;
+    // with what each one cost, to sort them by
+    let mut risks: Vec<(i32, String)> = Vec::new();
+    let pieces = [stars[0], stars[1], ship];
+    let has = |color: Color|pieces.iter().any(|id|id.color() == color);
+
+    for (other, sizes) in opponent_homes{
+        match home_distance(&[stars[0].size(), stars[1].size()], sizes, game_sizes){
+            1 => {
+                score -= 6;
+                risks.push((6, format!("next to player {other}'s homeworld, open to a direct assault")));
+            },
+            2 => {
+                score -= 3;
+                risks.push((3, format!("small universe with player {other}: only two moves apart")));
+            },
+            _ => score += 1,
+        }
+    }
+    if stars[0].size() == stars[1].size() {
+        score -= 3;
+        risks.push((3, format!("both stars are {:?}, connected to every system without that size", stars[0].size())));
+    }
+    for color in COLORS{
+        if pieces.iter().all(|id|id.color() == color) {
+            score -= 4;
+            risks.push((4, format!("three {color:?} pieces at home, one more causes a catastrophe")));
+        }
+    }
+    if has(Color::Green) { score += 3 } else { risks.push((3, "no green: ships can't be built at home".to_string())) }
+    if has(Color::Yellow) { score += 2 } else { risks.push((2, "no yellow: ships can't leave home without a sacrifice".to_string())) }
+    if has(Color::Blue) { score += 2 } else { risks.push((2, "no blue: ships can't be traded at home".to_string())) }
+    match ship.size(){
+        Size::Large | Size::Huge => score += 3,
+        Size::Medium => {
+            score += 1;
+            risks.push((2, "a medium first ship is easier to capture than a large one".to_string()));
+        },
+        Size::Small => risks.push((3, "a small first ship is captured by anything".to_string())),
+    }
+    // stable, so equal costs keep the order above
+    risks.sort_by_key(|(cost, _)|-cost);
+    let risks = risks.into_iter().map(|(_, risk)|risk).collect();
+    HomeworldAdvice { stars, ship, score, risks }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::piece;
+
+    #[test]
+    fn distances_between_homes(){
+        use Size::*;
+        let sizes = [Small, Medium, Large];
+        assert_eq!(home_distance(&[Medium, Medium], &[Small, Large], &sizes), 1);
+        assert_eq!(home_distance(&[Small, Medium], &[Medium, Small], &sizes), 2);
+        assert_eq!(home_distance(&[Small, Medium], &[Small, Large], &sizes), 3);
+        // a fourth size leaves room in between
+        assert_eq!(home_distance(&[Small, Medium], &[Small, Large], &[Small, Medium, Large, Huge]), 2);
+    }
+
+    #[test]
+    fn small_universe_is_ranked_below_large_universe(){
+        use {Color::*, Size::*};
+        let board = Board::new(2)
+            .apply_action(Action::CreateHomeworld { stars: [piece(Red, Small), piece(Blue, Medium)], ship: piece(Green, Large) })
+            .unwrap();
+        let advice = board.homeworld_advice(1);
+        assert!(advice.windows(2).all(|w|w[0].score >= w[1].score));
+
+        let best = &advice[0];
+        let sizes = [best.stars[0].size(), best.stars[1].size()];
+        assert_eq!(home_distance(&sizes, &[Small, Medium], &[Small, Medium, Large]), 3);
+        assert_eq!(best.ship.size(), Large);
+
+        let small_universe = advice.iter()
+            .find(|advice|[advice.stars[0].size(), advice.stars[1].size()] == [Small, Medium])
+            .unwrap();
+        assert!(small_universe.score < best.score);
+        assert!(small_universe.risks[0].contains("small universe"));
+
+        // found after the shared size, listed before it
+        let next_door = advice.iter()
+            .find(|advice|[advice.stars[0].size(), advice.stars[1].size()] == [Large, Large] && advice.ship.size() == Small)
+            .unwrap();
+        assert!(next_door.risks[0].contains("next to player 0"));
+        assert!(next_door.risks[1].contains("both stars are Large"));
+    }
+}
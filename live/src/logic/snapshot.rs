@@ -0,0 +1,135 @@
+use super::*;
+use super::record::{piece_code, parse_piece};
+
+/// where a 64 bit FNV-1a hash starts
+pub(super) const FNV_START: u64 = 0xcbf29ce484222325;
+
+/// 64 bit FNV-1a of `bytes`, continuing from `hash`. Simple and the same
+/// everywhere, but anyone can compute it, so it only catches accidents
+/// and careless edits.
+pub(super) fn fnv1a(hash: u64, bytes: &[u8])->u64{
+    bytes.iter().fold(hash, |hash, byte|(hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// piece codes, `-` for none
+fn codes(ids: &[Id])->String{
+    let codes: Vec<String> = ids.iter().map(|id|piece_code(*id)).collect();
+    if codes.is_empty() { "-".to_string() } else { codes.join(" ") }
+}
+
+/// the whole board on one line: player count, player to move, the piece set
+/// unless it is the standard one, and every system with its stars, owner and
+/// each player's ships, like `2 0 | r1a b2a home 0: g3a / - | y3a: - / r1b`.
+/// Systems and ships are sorted, so boards with the same pieces in the same
+/// places read the same no matter how they were played.
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut systems: Vec<String> = self.systems().map(|(_, system)|{
+            let home = system.home.map_or(String::new(), |player|format!(" home {player}"));
+            let fleets: Vec<String> = system.ships.iter().map(|ships|{
+                let mut ships = ships.clone();
+                ships.sort();
+                codes(&ships)
+            }).collect();
+            format!("{}{home}: {}", codes(&system.stars), fleets.join(" / "))
+        }).collect();
+        systems.sort();
+        write!(f, "{} {}", self.player_count, self.current_player)?;
+        if self.set != PieceSet::STANDARD {
+            write!(f, " {}", self.set)?;
+        }
+        for system in systems{
+            write!(f, " | {system}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Board{
+    /// reads a board written by its `Display`, none unless every piece is accounted for
+    pub(crate) fn parse(text: &str)->Option<Board>{
+        let mut parts = text.split('|');
+        let mut header = parts.next()?.split_whitespace();
+        let player_count: u8 = header.next()?.parse().ok()?;
+        let current_player: u8 = header.next()?.parse().ok().filter(|player|*player < player_count)?;
+        let set = match header.next(){
+            Some(set) => set.parse().ok()?,
+            None => PieceSet::STANDARD,
+        };
+        if header.next().is_some() {
+            return None;
+        }
+        let pieces = |text: &str|->Option<Vec<Id>>{
+            match text.trim(){
+                "-" => Some(Vec::new()),
+                text => text.split_whitespace().map(parse_piece).collect(),
+            }
+        };
+
+        let mut board = Board { current_player, ..Board::with_set(player_count, set) };
+        for part in parts{
+            let (stars, ships) = part.split_once(':')?;
+            let (stars, home) = match stars.split_once(" home "){
+                Some((stars, home)) => (stars, Some(home.trim().parse().ok()?)),
+                None => (stars, None),
+            };
+            let stars = pieces(stars)?;
+            let ships = ships.split('/').map(pieces).collect::<Option<Box<[Vec<Id>]>>>()?;
+            for id in stars.iter().chain(ships.iter().flatten()){
+                if !board.bank.remove(id) {
+                    return None;
+                }
+            }
+            board.insert_system(System { stars, home, ships });
+        }
+        board.validate().ok()?;
+        Some(board)
+    }
+
+    /// a hash of the board that stays the same across builds and machines,
+    /// for checking that two copies of a game agree
+    pub(crate) fn checksum(&self)->u64{
+        fnv1a(FNV_START, self.to_string().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system, random_start};
+
+    #[test]
+    fn snapshots_round_trip(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Red, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Yellow, Large)], None, [vec![piece(Blue, Small)], vec![piece(Red, Medium)]]),
+        ]);
+        let text = board.to_string();
+        assert_eq!(text, "2 0 | r1a b2a home 0: g3a / - | y3a: b1a / r2a");
+        let parsed = Board::parse(&text).unwrap();
+        assert_eq!(parsed, board);
+        assert_eq!(parsed.checksum(), board.checksum());
+
+        let mut rng = Rng::new(5);
+        for _ in 0..20{
+            let board = random_start(2, &mut rng);
+            assert_eq!(Board::parse(&board.to_string()).map(|parsed|parsed.checksum()), Some(board.checksum()));
+        }
+
+        // whose turn it is counts
+        let mut next = board.clone();
+        next.end_turn();
+        assert_ne!(next.checksum(), board.checksum());
+        // a piece in two places, or a missing one
+        assert_eq!(Board::parse("2 0 | r1a home 0: r1a / -"), None);
+        assert_eq!(Board::parse("2 0 | y3a: - / -"), None);
+        assert_eq!(Board::parse("2 2"), None);
+
+        // other piece sets are named, their pieces count as all there is
+        let reduced = Board::with_set(2, "gy/13/1".parse().unwrap());
+        assert_eq!(reduced.to_string(), "2 0 gy/13/1");
+        assert_eq!(Board::parse("2 0 gy/13/1"), Some(reduced));
+        assert_eq!(Board::parse("2 0 gy/13/1 | r1a: g1a / -"), None);
+    }
+}
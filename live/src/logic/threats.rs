@@ -0,0 +1,133 @@
+use super::*;
+
+/// what the other players could do to one player on their next turn, found
+/// among the turns `legal_turns` gives them
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) struct Threats{
+    /// the player's ships an opponent could capture, with their system
+    pub(crate) attackable: Vec<(Id, SystemId)>,
+    /// catastrophes an opponent could cause where the player has a ship or
+    /// their homeworld
+    pub(crate) catastrophes: Vec<(SystemId, Color)>,
+    /// opponent ships that could move into the player's homeworld, with their system
+    pub(crate) assault: Vec<(Id, SystemId)>,
+    pub(crate) homeworld: Option<SystemId>,
+}
+
+impl Threats{
+    /// a catastrophe at home can end the game on its own
+    pub(crate) fn homeworld_catastrophe(&self)->bool{
+        self.catastrophes.iter().any(|(system, _)|Some(*system) == self.homeworld)
+    }
+}
+
+/// adds `threat` unless it is listed already, many turns share a capture or a move
+fn add<T: PartialEq>(list: &mut Vec<T>, threat: T){
+    if !list.contains(&threat) {
+        list.push(threat);
+    }
+}
+
+impl Board{
+    /// ships `player` could sacrifice for an action of `color` anywhere
+    pub(super) fn sacrificable(&self, player: u8, color: Color)->impl Iterator<Item = Id> + '_{
+        self.systems()
+            .flat_map(move |(_, system)|system.ships[player as usize].iter().copied())
+            .filter(move |ship|ship.color() == color)
+    }
+
+    /// every turn each opponent of `player` with a homeworld could take if
+    /// it were their turn now, as `legal_turns` lists them
+    pub(super) fn opponent_turns(&self, player: u8)->Vec<(u8, Vec<Action>)>{
+        (0..self.player_count)
+            .filter(|opponent|*opponent != player && self.homeworld(*opponent).is_some())
+            .flat_map(|opponent|{
+                let mut board = self.clone();
+                board.current_player = opponent;
+                board.legal_turns().into_iter().map(move |(turn, _)|(opponent, turn))
+            })
+            .collect()
+    }
+
+    /// which captures, catastrophes and moves into `player`'s homeworld the
+    /// opponents' turns contain
+    pub(crate) fn threats(&self, player: u8)->Threats{
+        let mut threats = Threats { homeworld: self.homeworld(player), ..Default::default() };
+        let occupied = |id: SystemId|self.system(id)
+            .is_some_and(|system|!system.ships[player as usize].is_empty() || system.home == Some(player));
+        for (_, turn) in self.opponent_turns(player){
+            for action in turn{
+                match action{
+                    Action::CaptureShip { ship, star } => {
+                        // the player's ships don't move during someone else's turn
+                        let Some(id) = self.find_system(star) else {
+                            continue;
+                        };
+                        if self.system(id).is_some_and(|system|system.ships[player as usize].contains(&ship)) {
+                            add(&mut threats.attackable, (ship, id));
+                        }
+                    },
+                    Action::Catastrophe { star, color } => {
+                        if let Some(id) = self.find_system(star).filter(|id|occupied(*id)) {
+                            add(&mut threats.catastrophes, (id, color));
+                        }
+                    },
+                    Action::MoveShip { ship, start_star, end_star } => {
+                        let home = threats.homeworld.and_then(|id|self.system(id));
+                        // ships that got to `start_star` this turn aren't on the board yet
+                        let from = self.find_system(start_star);
+                        if let (Some(home), Some(from)) = (home, from) {
+                            if home.stars.contains(&end_star) {
+                                add(&mut threats.assault, (ship, from));
+                            }
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        threats
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{piece, board, system};
+
+    #[test]
+    fn bigger_ships_with_red_can_capture(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Yellow, Small), piece(Blue, Medium)], Some(0), [vec![piece(Green, Large)], vec![]]),
+            system(vec![piece(Green, Small), piece(Blue, Large)], Some(1), [vec![], vec![piece(Yellow, Large)]]),
+            system(vec![piece(Red, Small)], None, [vec![piece(Green, Medium)], vec![Id::new(Blue, Large, 1)]]),
+            system(vec![Id::new(Green, Medium, 1)], None, [vec![Id::new(Yellow, Small, 1)], vec![piece(Yellow, Medium)]]),
+            system(vec![piece(Blue, Small)], None, [vec![Id::new(Green, Large, 1)], vec![piece(Red, Medium)]]),
+        ]);
+        let threats = board.threats(0);
+        let attackable: Vec<Id> = threats.attackable.iter().map(|(ship, _)|*ship).collect();
+        // red star, bigger attacker
+        assert!(attackable.contains(&piece(Green, Medium)));
+        // no red there, but player 1 can sacrifice its red ship elsewhere
+        assert!(attackable.contains(&Id::new(Yellow, Small, 1)));
+        // red is there, the attacker is too small
+        assert!(!attackable.contains(&Id::new(Green, Large, 1)));
+        assert!(board.threats(1).attackable.is_empty());
+    }
+
+    #[test]
+    fn homeworld_dangers(){
+        use {Color::*, Size::*};
+        let board = board(vec![
+            system(vec![piece(Blue, Small), piece(Yellow, Large)], Some(0), [vec![Id::new(Blue, Small, 1), Id::new(Blue, Large, 1)], vec![]]),
+            system(vec![piece(Yellow, Medium)], Some(1), [vec![], vec![Id::new(Blue, Small, 2)]]),
+        ]);
+        // the small blue moves in as the fourth blue piece
+        let threats = board.threats(0);
+        assert!(threats.homeworld_catastrophe());
+        assert_eq!(threats.catastrophes, vec![(board.homeworld(0).unwrap(), Blue)]);
+        assert_eq!(threats.assault, vec![(Id::new(Blue, Small, 2), board.homeworld(1).unwrap())]);
+        assert!(threats.attackable.is_empty());
+    }
+}
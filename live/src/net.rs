@@ -0,0 +1,747 @@
+//! games over TCP: one authoritative server, players and spectators as clients.
+//!
+//! Both sides send lines. A client says who it is and then plays:
+//!
+//! ```text
+//! hello player | hello player N | hello spectator     N takes back a seat after a disconnect
+//! turn TURN                         a whole turn in the game notation
+//! resign | offer | accept | decline
+//! sync                              asks for a snapshot
+//! ```
+//!
+//! The server checks every turn on its own `Game` and sends it on to everyone:
+//!
+//! ```text
+//! welcome player N | welcome spectator
+//! game players N limit L|none       followed by a snapshot
+//! snapshot BOARD                    the whole board, see `Board`'s `Display`
+//! turn TURN
+//! decision N resign|offer|accept|decline
+//! checksum HEX                      of the board after every turn, decision and snapshot
+//! joined N | left N                 a player's connection came or went
+//! over TEXT                         the outcome, the server closes afterwards
+//! error TEXT                        only to the client that caused it
+//! ```
+//!
+//! A client whose board doesn't match a checksum asks for a snapshot and starts over from it.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crate::logic::{self, Action, Board, Decision, Game};
+
+/// what the server's threads tell the game loop
+enum Incoming{
+    Connected(usize, TcpStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+/// a connection as the server sees it
+struct Peer{
+    stream: TcpStream,
+    /// none until the client said hello
+    role: Option<Option<u8>>,
+}
+
+impl Peer{
+    fn send(&mut self, line: &str){
+        // a broken connection shows up as a disconnect from its reader
+        let _ = writeln!(self.stream, "{line}");
+    }
+}
+
+fn checksum_line(game: &Game)->String{
+    format!("checksum {:016x}", game.board().checksum())
+}
+
+/// the lines that bring a client's copy of `game` up to date: the board,
+/// a draw on offer and the checksum to compare against
+fn snapshot(game: &Game)->Vec<String>{
+    let mut lines = vec![format!("snapshot {}", game.board())];
+    if let Some(player) = game.draw_offer() {
+        lines.push(format!("decision {player} offer"));
+    }
+    lines.push(checksum_line(game));
+    lines
+}
+
+struct Server{
+    game: Game,
+    no_progress_limit: Option<u32>,
+    peers: HashMap<usize, Peer>,
+    /// the connection playing each seat
+    seats: Vec<Option<usize>>,
+}
+
+impl Server{
+    fn broadcast(&mut self, line: &str){
+        for peer in self.peers.values_mut().filter(|peer|peer.role.is_some()){
+            peer.send(line);
+        }
+    }
+
+    fn error(&mut self, id: usize, error: &str){
+        if let Some(peer) = self.peers.get_mut(&id) {
+            peer.send(&format!("error {error}"));
+        }
+    }
+
+    /// `role` is `player`, `player N` to take back seat N after a disconnect, or `spectator`
+    fn hello(&mut self, id: usize, role: &str)->Result<(), String>{
+        let seat = match role.split_whitespace().collect::<Vec<_>>().as_slice(){
+            ["player"] => Some(self.seats.iter().position(|seat|seat.is_none()).ok_or("every seat is taken")? as u8),
+            ["player", seat] => {
+                let seat: u8 = seat.parse().map_err(|_|format!("no seat {seat}"))?;
+                match self.seats.get(seat as usize){
+                    Some(None) => Some(seat),
+                    Some(Some(_)) => return Err(format!("seat {seat} is taken")),
+                    None => return Err(format!("no seat {seat}")),
+                }
+            },
+            ["spectator"] => None,
+            _ => return Err(format!("unknown role {role}")),
+        };
+        let limit = self.no_progress_limit.map_or("none".to_string(), |limit|limit.to_string());
+        let mut lines = vec![format!("game players {} limit {limit}", self.game.board().player_count())];
+        lines.extend(snapshot(&self.game));
+        let peer = self.peers.get_mut(&id).ok_or("not connected")?;
+        if peer.role.is_some() {
+            return Err("already said hello".to_string());
+        }
+        match seat{
+            Some(seat) => peer.send(&format!("welcome player {seat}")),
+            None => peer.send("welcome spectator"),
+        }
+        for line in lines{
+            peer.send(&line);
+        }
+        if let Some(outcome) = self.game.outcome() {
+            peer.send(&format!("over {outcome}"));
+        }
+        peer.role = Some(seat);
+        if let Some(seat) = seat {
+            self.seats[seat as usize] = Some(id);
+            self.broadcast(&format!("joined {seat}"));
+        }
+        Ok(())
+    }
+
+    /// handles one line from client `id`
+    fn command(&mut self, id: usize, line: &str)->Result<(), String>{
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        if command == "hello" {
+            return self.hello(id, args.trim());
+        }
+        let role = self.peers.get(&id).and_then(|peer|peer.role).ok_or("say hello first")?;
+        if command == "sync" {
+            let lines = snapshot(&self.game);
+            if let Some(peer) = self.peers.get_mut(&id) {
+                for line in lines{
+                    peer.send(&line);
+                }
+            }
+            return Ok(());
+        }
+        let seat = role.ok_or("spectators can't play")?;
+        if self.game.outcome().is_some() {
+            return Err("the game is over".to_string());
+        }
+        if let Ok(decision) = command.parse::<Decision>() {
+            self.game.decide(seat, decision).ok_or(format!("can't {command} now"))?;
+            self.broadcast(&format!("decision {seat} {command}"));
+            self.broadcast(&checksum_line(&self.game));
+            return Ok(());
+        }
+        if command != "turn" {
+            return Err(format!("unknown command {command}"));
+        }
+        if seat != self.game.board().current_player() {
+            return Err("it isn't your turn".to_string());
+        }
+        let turn = logic::parse_turn(args).ok_or(format!("can't read turn {args}"))?;
+        // check the whole turn first so a bad one leaves no trace
+        if self.game.board().legal_turn(&turn).is_none() {
+            return Err(format!("illegal turn {args}"));
+        }
+        for action in &turn{
+            self.game.apply_action(*action);
+        }
+        self.game.end_turn();
+        self.broadcast(&format!("turn {}", logic::format_turn(&turn)));
+        self.broadcast(&checksum_line(&self.game));
+        Ok(())
+    }
+}
+
+/// runs one game for clients connecting to `listener` and returns it once it is over
+pub(crate) fn serve(listener: TcpListener, player_count: u8, no_progress_limit: Option<u32>)->io::Result<Game>{
+    let (sender, incoming) = mpsc::channel();
+    std::thread::spawn(move||{
+        for (id, stream) in listener.incoming().enumerate(){
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            // the game is over once nobody listens
+            if sender.send(Incoming::Connected(id, stream)).is_err() {
+                return;
+            }
+            let sender = sender.clone();
+            std::thread::spawn(move||{
+                for line in BufReader::new(reader).lines(){
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if sender.send(Incoming::Line(id, line)).is_err() {
+                        return;
+                    }
+                }
+                let _ = sender.send(Incoming::Disconnected(id));
+            });
+        }
+    });
+
+    let mut server = Server {
+        game: Game::new(player_count, no_progress_limit),
+        no_progress_limit,
+        peers: HashMap::new(),
+        seats: vec![None; player_count as usize],
+    };
+    while server.game.outcome().is_none() {
+        match incoming.recv().map_err(|_|io::Error::other("the listener stopped"))?{
+            Incoming::Connected(id, stream) => {
+                server.peers.insert(id, Peer { stream, role: None });
+            },
+            Incoming::Line(id, line) => {
+                if let Err(error) = server.command(id, &line) {
+                    server.error(id, &error);
+                }
+            },
+            Incoming::Disconnected(id) => {
+                server.peers.remove(&id);
+                if let Some(seat) = server.seats.iter().position(|seat|*seat == Some(id)) {
+                    server.seats[seat] = None;
+                    server.broadcast(&format!("left {seat}"));
+                }
+            },
+        }
+    }
+    if let Some(outcome) = server.game.outcome() {
+        server.broadcast(&format!("over {outcome}"));
+    }
+    // the readers hold copies of the streams, shutting down closes them for good
+    for peer in server.peers.values(){
+        let _ = peer.stream.shutdown(Shutdown::Both);
+    }
+    Ok(server.game)
+}
+
+/// turns without anything entering or leaving play before the game is a draw, as in the window
+const NO_PROGRESS_LIMIT: u32 = 50;
+
+/// serves one two player game at `address` and prints its record once it is over
+pub fn run_server(address: &str)->io::Result<()>{
+    let listener = TcpListener::bind(address)?;
+    println!("waiting for players on {}", listener.local_addr()?);
+    let game = serve(listener, 2, Some(NO_PROGRESS_LIMIT))?;
+    print!("{}", game.record().narrated(&[]));
+    Ok(())
+}
+
+/// a line from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Message{
+    /// our seat, none for a spectator
+    Welcome(Option<u8>),
+    Game{ player_count: u8, no_progress_limit: Option<u32> },
+    Snapshot(Board),
+    Turn(Vec<Action>),
+    Decision(u8, Decision),
+    Checksum(u64),
+    Joined(u8),
+    Left(u8),
+    Over(String),
+    Error(String),
+}
+
+impl Message{
+    fn parse(line: &str)->Option<Message>{
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let words: Vec<&str> = args.split_whitespace().collect();
+        let message = match (command, words.as_slice()){
+            ("welcome", ["player", seat]) => Message::Welcome(Some(seat.parse().ok()?)),
+            ("welcome", ["spectator"]) => Message::Welcome(None),
+            ("game", ["players", player_count, "limit", limit]) => Message::Game {
+                player_count: player_count.parse().ok()?,
+                no_progress_limit: match *limit{
+                    "none" => None,
+                    limit => Some(limit.parse().ok()?),
+                },
+            },
+            ("snapshot", _) => Message::Snapshot(Board::parse(args)?),
+            ("turn", _) => Message::Turn(logic::parse_turn(args)?),
+            ("decision", [player, decision]) => Message::Decision(player.parse().ok()?, decision.parse().ok()?),
+            ("checksum", [checksum]) => Message::Checksum(u64::from_str_radix(checksum, 16).ok()?),
+            ("joined", [seat]) => Message::Joined(seat.parse().ok()?),
+            ("left", [seat]) => Message::Left(seat.parse().ok()?),
+            ("over", _) => Message::Over(args.to_string()),
+            ("error", _) => Message::Error(args.to_string()),
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+/// a connection to `address` and the lines coming in on it
+fn open(address: SocketAddr)->io::Result<(TcpStream, Receiver<String>)>{
+    let stream = TcpStream::connect(address)?;
+    let reader = BufReader::new(stream.try_clone()?);
+    let (sender, messages) = mpsc::channel();
+    std::thread::spawn(move||{
+        for line in reader.lines(){
+            match line{
+                Ok(line) => if sender.send(line).is_err() {
+                    return;
+                },
+                Err(_) => return,
+            }
+        }
+    });
+    Ok((stream, messages))
+}
+
+/// a connection to a server that keeps a copy of the game up to date
+/// and checks it against the server's checksums
+pub(crate) struct Client{
+    address: SocketAddr,
+    stream: TcpStream,
+    messages: Receiver<String>,
+    /// our seat, none for spectators and until the server welcomed us
+    pub(crate) seat: Option<u8>,
+    pub(crate) game: Game,
+    no_progress_limit: Option<u32>,
+    /// the server closed the connection
+    pub(crate) closed: bool,
+    /// a snapshot was asked for and hasn't come yet
+    syncing: bool,
+    /// how often our copy of the board didn't match the server's
+    pub(crate) desyncs: u32,
+}
+
+impl Client{
+    /// joins the game at `address`, as a player or to watch
+    pub(crate) fn connect(address: impl ToSocketAddrs, player: bool)->io::Result<Client>{
+        Client::start(address, if player { "hello player" } else { "hello spectator" })
+    }
+
+    /// joins the game at `address` as the player in `seat`
+    #[cfg(feature = "window")]
+    pub(crate) fn join(address: impl ToSocketAddrs, seat: u8)->io::Result<Client>{
+        Client::start(address, &format!("hello player {seat}"))
+    }
+
+    fn start(address: impl ToSocketAddrs, hello: &str)->io::Result<Client>{
+        let address = address.to_socket_addrs()?.next()
+            .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "no address"))?;
+        let (stream, messages) = open(address)?;
+        let mut client = Client {
+            address,
+            stream,
+            messages,
+            seat: None,
+            game: Game::new(2, None),
+            no_progress_limit: None,
+            closed: false,
+            syncing: false,
+            desyncs: 0,
+        };
+        client.send(hello)?;
+        Ok(client)
+    }
+
+    /// connects again after the connection dropped and takes back our seat,
+    /// the server answers with a snapshot
+    pub(crate) fn reconnect(&mut self)->io::Result<()>{
+        let (stream, messages) = open(self.address)?;
+        let _ = self.stream.shutdown(Shutdown::Both);
+        self.stream = stream;
+        self.messages = messages;
+        self.closed = false;
+        self.syncing = false;
+        match self.seat{
+            Some(seat) => self.send(&format!("hello player {seat}")),
+            None => self.send("hello spectator"),
+        }
+    }
+
+    fn send(&mut self, line: &str)->io::Result<()>{
+        writeln!(self.stream, "{line}")?;
+        self.stream.flush()
+    }
+
+    /// sends a whole turn, the server answers with it or with an error
+    pub(crate) fn play(&mut self, turn: &[Action])->io::Result<()>{
+        self.send(&format!("turn {}", logic::format_turn(turn)))
+    }
+
+    pub(crate) fn decide(&mut self, decision: Decision)->io::Result<()>{
+        self.send(&decision.to_string())
+    }
+
+    /// applies a message to our copy of the game
+    fn handle(&mut self, message: &Message){
+        match message{
+            Message::Welcome(seat) => self.seat = *seat,
+            Message::Game { player_count, no_progress_limit } => {
+                self.no_progress_limit = *no_progress_limit;
+                self.game = Game::new(*player_count, *no_progress_limit);
+            },
+            // the history before the snapshot is gone, the server still
+            // decides repetitions and announces the outcome
+            Message::Snapshot(board) => {
+                self.game = Game::from_board(board.clone(), self.no_progress_limit);
+                self.syncing = false;
+            },
+            Message::Turn(turn) => {
+                for action in turn{
+                    self.game.apply_action(*action);
+                }
+                self.game.end_turn();
+            },
+            Message::Decision(player, decision) => {
+                self.game.decide(*player, *decision);
+            },
+            Message::Checksum(checksum) if !self.syncing && self.game.board().checksum() != *checksum => {
+                self.desyncs += 1;
+                self.syncing = true;
+                let _ = self.send("sync");
+            },
+            _ => {},
+        }
+    }
+
+    /// the next message, waiting up to `timeout` for it. None when nothing
+    /// came in time or once the server closed the connection, see `closed`.
+    pub(crate) fn receive(&mut self, timeout: Duration)->Option<Message>{
+        loop{
+            let line = match self.messages.recv_timeout(timeout){
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.closed = true;
+                    return None;
+                },
+            };
+            if let Some(message) = Message::parse(&line) {
+                self.handle(&message);
+                return Some(message);
+            }
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // the reader thread holds a copy of the stream, so closing ours isn't enough
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// `r1a b2a: 0 g3a | 1 -`, one line per system
+fn describe(game: &Game)->Vec<String>{
+    game.board().systems().map(|(_, system)|{
+        let stars: Vec<String> = system.stars.iter().map(|star|logic::piece_code(*star)).collect();
+        let fleets: Vec<String> = system.ships.iter().enumerate().map(|(player, ships)|{
+            let ships: Vec<String> = ships.iter().map(|ship|logic::piece_code(*ship)).collect();
+            let ships = if ships.is_empty() { "-".to_string() } else { ships.join(" ") };
+            format!("{player} {ships}")
+        }).collect();
+        let home = system.home.map_or(String::new(), |player|format!(" (home {player})"));
+        format!("{}{home}: {}", stars.join(" "), fleets.join(" | "))
+    }).collect()
+}
+
+/// a terminal client: prints the game as it goes and sends what is typed,
+/// `turn ...`, `resign`, `offer`, `accept` or `decline`
+pub fn run_client(address: &str, player: bool)->io::Result<()>{
+    let mut client = Client::connect(address, player)?;
+    let (sender, typed) = mpsc::channel();
+    std::thread::spawn(move||{
+        for line in io::stdin().lock().lines(){
+            if line.map(|line|sender.send(line)).is_err() {
+                return;
+            }
+        }
+    });
+    let mut over = false;
+    loop{
+        // everything that already arrived, so catching up prints the board once
+        let mut received = false;
+        let mut played = false;
+        let wait = Duration::from_millis(50);
+        while let Some(message) = client.receive(if received { Duration::ZERO } else { wait }){
+            received = true;
+            match message{
+                Message::Turn(turn) => {
+                    println!("played: {}", logic::format_turn(&turn));
+                    played = true;
+                },
+                Message::Snapshot(_) => played = true,
+                Message::Checksum(_) => {},
+                Message::Welcome(Some(seat)) => println!("you are player {seat}"),
+                Message::Welcome(None) => println!("watching"),
+                Message::Game { .. } => {},
+                Message::Decision(player, decision) => println!("player {player}: {decision}"),
+                Message::Joined(player) => println!("player {player} joined"),
+                Message::Left(player) => println!("player {player} left"),
+                Message::Over(outcome) => {
+                    println!("{outcome}");
+                    over = true;
+                },
+                Message::Error(error) => println!("error: {error}"),
+            }
+        }
+        if client.closed {
+            if over {
+                break;
+            }
+            println!("connection lost, reconnecting");
+            std::thread::sleep(Duration::from_secs(1));
+            if let Err(error) = client.reconnect() {
+                println!("{error}");
+                client.closed = true;
+            }
+            continue;
+        }
+        if played {
+            for line in describe(&client.game){
+                println!("  {line}");
+            }
+        }
+        if received && client.game.outcome().is_none() && client.seat == Some(client.game.board().current_player()) {
+            println!("your turn, player {}", client.game.board().current_player());
+        }
+
+        for line in typed.try_iter(){
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Ok(decision) = line.parse::<Decision>() {
+                client.decide(decision)?;
+                continue;
+            }
+            match logic::parse_turn(line.strip_prefix("turn ").unwrap_or(line)){
+                Some(turn) => client.play(&turn)?,
+                None => println!("can't read {line}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::logic::Engine;
+    use crate::logic::Weights;
+
+    fn start_server(player_count: u8)->(std::net::SocketAddr, std::thread::JoinHandle<io::Result<Game>>){
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        (address, std::thread::spawn(move||serve(listener, player_count, Some(50))))
+    }
+
+    /// waits for a message that `pick` accepts, skipping the rest
+    fn expect<T>(client: &mut Client, mut pick: impl FnMut(&Message)->Option<T>)->T{
+        loop{
+            let message = client.receive(Duration::from_secs(10)).expect("the server answers");
+            if let Some(found) = pick(&message) {
+                return found;
+            }
+        }
+    }
+
+    #[test]
+    fn two_players_and_a_spectator(){
+        let (address, server) = start_server(2);
+        let mut players = [Client::connect(address, true).unwrap(), Client::connect(address, true).unwrap()];
+        for player in &mut players{
+            expect(player, |message|matches!(message, Message::Welcome(Some(_))).then_some(()));
+        }
+        let mut seats: Vec<u8> = players.iter().map(|player|player.seat.unwrap()).collect();
+        seats.sort();
+        assert_eq!(seats, vec![0, 1]);
+        // players[first] moves first
+        let first = players.iter().position(|player|player.seat == Some(0)).unwrap();
+
+        let mut engine = Engine::new(Weights::default(), 0.0, 3);
+        let opening = engine.search(players[first].game.board(), None, logic::Limits::default()).turn;
+        // out of turn and illegal turns are refused
+        players[1 - first].play(&opening).unwrap();
+        assert_eq!(expect(&mut players[1 - first], |message|match message{
+            Message::Error(error) => Some(error.clone()),
+            _ => None,
+        }), "it isn't your turn");
+        players[first].play(&[opening[0], opening[0]]).unwrap();
+        expect(&mut players[first], |message|matches!(message, Message::Error(_)).then_some(()));
+
+        players[first].play(&opening).unwrap();
+        for player in &mut players{
+            let turn = expect(player, |message|match message{
+                Message::Turn(turn) => Some(turn.clone()),
+                _ => None,
+            });
+            assert_eq!(turn, opening);
+        }
+        assert_eq!(players[0].game.board(), players[1].game.board());
+
+        // a spectator joining late catches up, a third player is turned away
+        let mut spectator = Client::connect(address, false).unwrap();
+        expect(&mut spectator, |message|matches!(message, Message::Checksum(_)).then_some(()));
+        assert_eq!(spectator.seat, None);
+        assert_eq!(spectator.game.board().checksum(), players[0].game.board().checksum());
+        assert_eq!(spectator.desyncs, 0);
+        let mut third = Client::connect(address, true).unwrap();
+        expect(&mut third, |message|matches!(message, Message::Error(_)).then_some(()));
+
+        players[first].decide(Decision::Resign).unwrap();
+        let outcome = expect(&mut spectator, |message|match message{
+            Message::Over(outcome) => Some(outcome.clone()),
+            _ => None,
+        });
+        let game = server.join().unwrap().unwrap();
+        assert_eq!(outcome, game.outcome().unwrap().to_string());
+        assert_eq!(spectator.game.outcome(), game.outcome());
+    }
+
+    #[test]
+    fn rule_breaking_turns_are_refused(){
+        let (address, server) = start_server(2);
+        let mut players = [Client::connect(address, true).unwrap(), Client::connect(address, true).unwrap()];
+        for player in &mut players{
+            expect(player, |message|matches!(message, Message::Welcome(Some(_))).then_some(()));
+        }
+        players.sort_by_key(|player|player.seat);
+        for seat in 0..2{
+            let turn = play_a_turn(&mut players[seat]);
+            for player in &mut players{
+                expect(player, |message|(*message == Message::Turn(turn.clone())).then_some(()));
+            }
+        }
+
+        // two actions that are each fine on their own but no legal turn together
+        let board = players[0].game.board().clone();
+        let singles: Vec<Action> = board.legal_turns().into_iter()
+            .filter_map(|(turn, _)|match turn[..]{
+                [action] => Some(action),
+                _ => None,
+            })
+            .collect();
+        let cheat = singles.iter()
+            .flat_map(|a|singles.iter().map(move |b|[*a, *b]))
+            .find(|turn|{
+                let applies = turn.iter().try_fold(board.clone(), |board, action|board.apply_action(*action)).is_some();
+                applies && board.legal_turn(turn).is_none()
+            })
+            .expect("a second action without a sacrifice");
+        players[0].play(&cheat).unwrap();
+        let error = expect(&mut players[0], |message|match message{
+            Message::Error(error) => Some(error.clone()),
+            _ => None,
+        });
+        assert_eq!(error, format!("illegal turn {}", logic::format_turn(&cheat)));
+        assert_eq!(players[0].game.board(), &board);
+
+        players[1].decide(Decision::Resign).unwrap();
+        let game = server.join().unwrap().unwrap();
+        assert_eq!(game.board(), &board);
+    }
+
+    #[test]
+    fn seats_free_up_on_disconnect(){
+        let (address, server) = start_server(2);
+        let mut first = Client::connect(address, true).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Welcome(_)).then_some(()));
+        let second = Client::connect(address, true).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Joined(1)).then_some(()));
+        drop(second);
+        expect(&mut first, |message|matches!(message, Message::Left(1)).then_some(()));
+
+        let mut again = Client::connect(address, true).unwrap();
+        expect(&mut again, |message|matches!(message, Message::Welcome(Some(1))).then_some(()));
+        again.decide(Decision::Resign).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Over(_)).then_some(()));
+        assert_eq!(server.join().unwrap().unwrap().outcome().unwrap().winner, Some(0));
+        assert_eq!(Message::parse("game players 2 limit none"), Some(Message::Game { player_count: 2, no_progress_limit: None }));
+    }
+
+    /// plays the engine's turn for `client`, who has to be the one to move
+    fn play_a_turn(client: &mut Client)->Vec<Action>{
+        let mut engine = Engine::new(Weights::default(), 0.0, 5);
+        let turn = engine.search(client.game.board(), None, logic::Limits::default()).turn;
+        client.play(&turn).unwrap();
+        turn
+    }
+
+    fn checksum(client: &mut Client)->u64{
+        expect(client, |message|match message{
+            Message::Checksum(checksum) => Some(*checksum),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn reconnects_and_resyncs(){
+        let (address, server) = start_server(2);
+        let mut first = Client::connect(address, true).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Welcome(Some(0))).then_some(()));
+        let mut second = Client::connect(address, true).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Joined(1)).then_some(()));
+        checksum(&mut second);
+
+        play_a_turn(&mut first);
+        let after_opening = checksum(&mut first);
+        assert_eq!(checksum(&mut second), after_opening);
+
+        // the second player's connection drops and comes back to the same seat
+        second.stream.shutdown(Shutdown::Both).unwrap();
+        while !second.closed {
+            second.receive(Duration::from_secs(10));
+        }
+        expect(&mut first, |message|matches!(message, Message::Left(1)).then_some(()));
+        second.reconnect().unwrap();
+        expect(&mut second, |message|matches!(message, Message::Snapshot(_)).then_some(()));
+        assert_eq!(checksum(&mut second), after_opening);
+        assert_eq!(second.game.board().checksum(), after_opening);
+        assert_eq!(second.seat, Some(1));
+
+        play_a_turn(&mut second);
+        let checksum_first = checksum(&mut first);
+        assert_eq!(first.game.board().checksum(), checksum_first);
+        assert_eq!(checksum(&mut second), checksum_first);
+
+        // a copy that went wrong is noticed on the next turn and replaced
+        second.game = Game::new(2, None);
+        play_a_turn(&mut first);
+        let checksum_first = checksum(&mut first);
+        assert_eq!(checksum(&mut second), checksum_first);
+        assert_eq!(second.desyncs, 1);
+        expect(&mut second, |message|matches!(message, Message::Snapshot(_)).then_some(()));
+        assert_eq!(checksum(&mut second), checksum_first);
+        assert_eq!(second.game.board().checksum(), checksum_first);
+        assert_eq!((first.desyncs, second.desyncs), (0, 1));
+
+        second.decide(Decision::Resign).unwrap();
+        expect(&mut first, |message|matches!(message, Message::Over(_)).then_some(()));
+        assert_eq!(server.join().unwrap().unwrap().outcome().unwrap().winner, Some(0));
+    }
+}
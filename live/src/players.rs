@@ -0,0 +1,521 @@
+//! who plays a seat: someone at this computer, the built-in engine, an engine
+//! in another process or a player at the other end of a game server
+
+#[cfg(feature = "window")]
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+#[cfg(feature = "window")]
+use std::time::Duration;
+
+use crate::logic::{Action, Board, Book, Engine, Game, Limits, Search};
+#[cfg(feature = "window")]
+use crate::logic::{Decision, Weights};
+#[cfg(feature = "window")]
+use crate::net::{Client, Message};
+use crate::protocol::ExternalEngine;
+
+/// something that happened in the game, for controllers that follow along
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Played{
+    Turn(u8, Vec<Action>),
+    #[cfg(feature = "window")]
+    Decision(u8, Decision),
+}
+
+/// decides the turns of one seat
+pub(crate) trait PlayerController{
+    /// the whole turn to play in `game`, asked again every frame until there is one.
+    /// `entered` is a turn put together in the window, only a local human takes it.
+    /// An error means the player can't go on, they resign.
+    fn poll_turn(&mut self, game: &Game, entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>;
+
+    /// someone at this computer, who plays through the window
+    #[cfg(feature = "window")]
+    fn is_local_human(&self)->bool{
+        false
+    }
+
+    /// every turn and decision once it was played, whoever made it.
+    /// An error is shown to the players, the game goes on.
+    fn observe(&mut self, _game: &Game, _played: &Played)->Result<(), String>{
+        Ok(())
+    }
+
+    /// decisions made out of turn, like a resignation coming in over the network.
+    /// An error is shown to the players, the game goes on.
+    #[cfg(feature = "window")]
+    fn poll_decisions(&mut self)->Result<Vec<Decision>, String>{
+        Ok(Vec::new())
+    }
+
+    /// the best turn found so far while thinking
+    #[cfg(feature = "window")]
+    fn progress(&self)->Option<Search>{
+        None
+    }
+}
+
+/// someone at this computer
+#[cfg(feature = "window")]
+pub(crate) struct Human;
+
+#[cfg(feature = "window")]
+impl PlayerController for Human {
+    fn poll_turn(&mut self, _game: &Game, entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>{
+        Ok(entered.take())
+    }
+
+    fn is_local_human(&self)->bool{
+        true
+    }
+}
+
+/// the built-in engine. It searches on a thread of its own so the window
+/// keeps drawing, a search for a position that went away is stopped and
+/// left to end on its own.
+pub(crate) struct Ai{
+    /// as the last finished search left it, workers search with a copy
+    engine: Engine,
+    book: Option<Arc<Book>>,
+    limits: Limits,
+    worker: Option<Worker>,
+    /// workers told to stop that may still be running
+    stopped: Vec<Worker>,
+}
+
+/// a search running on its own thread
+struct Worker{
+    /// the position being searched
+    board: Board,
+    stop: Arc<AtomicBool>,
+    #[cfg(feature = "window")]
+    progress: Arc<Mutex<Option<Search>>>,
+    thread: JoinHandle<(Engine, Search)>,
+}
+
+impl Ai{
+    pub(crate) fn new(engine: Engine, book: Option<Arc<Book>>, limits: Limits)->Self{
+        Ai { engine, book, limits, worker: None, stopped: Vec::new() }
+    }
+
+    fn start(&mut self, board: &Board){
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut engine = self.engine.clone();
+        engine.stop = Some(stop.clone());
+        let progress = Arc::new(Mutex::new(None));
+        let thread = {
+            let (board, book, limits) = (board.clone(), self.book.clone(), self.limits);
+            let (stop, progress) = (stop.clone(), progress.clone());
+            std::thread::spawn(move||{
+                let search = engine.search_with(&board, book.as_deref(), limits, |search|{
+                    if let Ok(mut progress) = progress.lock() {
+                        *progress = Some(search.clone());
+                    }
+                    !stop.load(Ordering::Relaxed)
+                });
+                (engine, search)
+            })
+        };
+        self.worker = Some(Worker {
+            board: board.clone(),
+            stop,
+            #[cfg(feature = "window")]
+            progress,
+            thread,
+        });
+    }
+
+    /// tells the worker to stop without waiting for it
+    fn cancel(&mut self){
+        if let Some(worker) = self.worker.take() {
+            worker.stop.store(true, Ordering::Relaxed);
+            self.stopped.push(worker);
+        }
+        self.stopped.retain(|worker|!worker.thread.is_finished());
+    }
+
+    /// the search of the worker, which has finished
+    fn finish(&mut self)->Option<Search>{
+        let worker = self.worker.take()?;
+        // none if the worker panicked
+        let (engine, search) = worker.thread.join().ok()?;
+        self.engine = engine;
+        Some(search)
+    }
+}
+
+impl PlayerController for Ai {
+    fn poll_turn(&mut self, game: &Game, _entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>{
+        match &self.worker{
+            Some(worker) if worker.board != *game.board() => {
+                self.cancel();
+                self.start(game.board());
+                Ok(None)
+            },
+            Some(worker) if worker.thread.is_finished() => match self.finish(){
+                Some(search) => Ok(Some(search.turn)),
+                None => Err("the engine crashed".to_string()),
+            },
+            Some(_) => Ok(None),
+            None => {
+                self.start(game.board());
+                Ok(None)
+            },
+        }
+    }
+
+    #[cfg(feature = "window")]
+    fn progress(&self)->Option<Search>{
+        self.worker.as_ref()?.progress.lock().ok()?.clone()
+    }
+}
+
+impl Drop for Ai {
+    fn drop(&mut self) {
+        // the threads run code from this library, which a hot reload unloads.
+        // A stopped search ends within the position it was looking at.
+        self.cancel();
+        for worker in self.stopped.drain(..){
+            let _ = worker.thread.join();
+        }
+    }
+}
+
+/// an engine in another process, see `protocol`. Its answers are read on a
+/// thread of their own, so nothing waits for one.
+pub(crate) struct External{
+    engine: ExternalEngine,
+    limits: Limits,
+}
+
+impl External{
+    pub(crate) fn new(engine: ExternalEngine, limits: Limits)->Self{
+        External { engine, limits }
+    }
+}
+
+impl PlayerController for External {
+    fn poll_turn(&mut self, game: &Game, _entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>{
+        if self.engine.asked() == Some(game.board()) {
+            return self.engine.answer();
+        }
+        self.engine.ask(game, self.limits)?;
+        Ok(None)
+    }
+}
+
+/// a player at the other end of a game server, see `net`. The connection holds
+/// the seat played here: its turns and decisions are sent on it, and the
+/// remote player's come back over it.
+#[cfg(feature = "window")]
+pub(crate) struct Remote{
+    client: Client,
+    /// the remote player's seat
+    seat: u8,
+    turns: VecDeque<Vec<Action>>,
+    decisions: Vec<Decision>,
+    /// what the server refused since it was last reported
+    refused: Vec<String>,
+}
+
+#[cfg(feature = "window")]
+impl Remote{
+    /// joins the server at `address` in `local_seat` to play against the one in `seat`
+    pub(crate) fn connect(address: &str, local_seat: u8, seat: u8)->Result<Self, String>{
+        let client = Client::join(address, local_seat).map_err(|error|format!("{address}: {error}"))?;
+        Ok(Remote { client, seat, turns: VecDeque::new(), decisions: Vec::new(), refused: Vec::new() })
+    }
+
+    /// takes in what arrived so far, connecting again if the connection dropped
+    fn pump(&mut self)->Result<(), String>{
+        loop{
+            // the client applies a turn as it reads it, so look at who moves first
+            let mover = self.client.game.board().current_player();
+            match self.client.receive(Duration::ZERO){
+                Some(Message::Turn(turn)) if mover == self.seat => self.turns.push_back(turn),
+                Some(Message::Decision(player, decision)) if player == self.seat => self.decisions.push(decision),
+                // refused before we got a seat, most likely it's taken
+                Some(Message::Error(error)) if self.client.seat.is_none() => return Err(error),
+                Some(Message::Error(error)) => self.refused.push(error),
+                Some(_) => {},
+                None => break,
+            }
+        }
+        if self.client.closed {
+            self.client.reconnect().map_err(|error|format!("lost the server: {error}"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "window")]
+impl PlayerController for Remote {
+    fn poll_turn(&mut self, _game: &Game, _entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>{
+        self.pump()?;
+        Ok(self.turns.pop_front())
+    }
+
+    fn observe(&mut self, _game: &Game, played: &Played)->Result<(), String>{
+        let sent = match played{
+            Played::Turn(player, turn) if *player != self.seat => self.client.play(turn),
+            Played::Decision(player, decision) if *player != self.seat => self.client.decide(*decision),
+            _ => return Ok(()),
+        };
+        sent.map_err(|error|format!("server: {error}"))
+    }
+
+    fn poll_decisions(&mut self)->Result<Vec<Decision>, String>{
+        let pumped = self.pump();
+        // a resignation can come just before the server closes the game
+        if !self.decisions.is_empty() {
+            return Ok(std::mem::take(&mut self.decisions));
+        }
+        pumped?;
+        if !self.refused.is_empty() {
+            let refused: Vec<String> = self.refused.drain(..).collect();
+            return Err(format!("server: {}", refused.join(", ")));
+        }
+        Ok(Vec::new())
+    }
+}
+
+/// what plays a seat, as written in a players file
+#[cfg(feature = "window")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Kind{
+    Human,
+    /// the built-in engine, with the weights from a file if one is given
+    Ai(Option<String>),
+    /// the command that starts an external engine
+    Engine(String),
+    /// the address of a game server
+    Network(String),
+}
+
+/// a player's name and what plays for them, seats are indexed by player
+#[cfg(feature = "window")]
+pub(crate) struct Seat{
+    pub(crate) name: String,
+    pub(crate) controller: Box<dyn PlayerController>,
+}
+
+#[cfg(feature = "window")]
+impl std::fmt::Debug for Seat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Seat").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+/// everyone at this computer
+#[cfg(feature = "window")]
+pub(crate) fn humans(player_count: u8)->Vec<Seat>{
+    (0..player_count).map(|player|Seat { name: format!("player {player}"), controller: Box::new(Human) }).collect()
+}
+
+/// one `NAME = KIND` line per seat in turn order, KIND being `human`, `ai [WEIGHTS]`,
+/// `engine COMMAND` or `network ADDRESS`. Empty lines and `#` comments are skipped.
+#[cfg(feature = "window")]
+pub(crate) fn parse_seats(text: &str)->Result<Vec<(String, Kind)>, String>{
+    let mut seats = Vec::new();
+    for (i, line) in text.lines().enumerate(){
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = ||format!("line {}: expected `NAME = human|ai|engine|network ...`, found `{line}`", i + 1);
+        let (name, kind) = line.split_once('=').ok_or_else(error)?;
+        let kind = kind.trim();
+        let (word, rest) = kind.split_once(' ').map_or((kind, ""), |(word, rest)|(word, rest.trim()));
+        let kind = match (word, rest){
+            ("human", "") => Kind::Human,
+            ("ai", "") => Kind::Ai(None),
+            ("ai", weights) => Kind::Ai(Some(weights.to_string())),
+            ("engine", command) if !command.is_empty() => Kind::Engine(command.to_string()),
+            ("network", address) if !address.is_empty() => Kind::Network(address.to_string()),
+            _ => return Err(error()),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(error());
+        }
+        seats.push((name.to_string(), kind));
+    }
+    Ok(seats)
+}
+
+/// starts the players for `seats`. The built-in engine plays with `weights` unless its
+/// line names a file, network seats play against the one seat played here.
+#[cfg(feature = "window")]
+pub(crate) fn start_seats(seats: &[(String, Kind)], weights: Weights, book: &Arc<Book>, limits: Limits)->Result<Vec<Seat>, String>{
+    let local: Vec<usize> = (0..seats.len()).filter(|i|!matches!(seats[*i].1, Kind::Network(_))).collect();
+    let networked = local.len() < seats.len();
+    if networked && local.len() != 1 {
+        return Err("a network game needs exactly one seat played here".to_string());
+    }
+    let mut started = Vec::new();
+    for (seat, (name, kind)) in seats.iter().enumerate(){
+        let controller: Box<dyn PlayerController> = match kind{
+            Kind::Human => Box::new(Human),
+            Kind::Ai(file) => {
+                let weights = match file{
+                    Some(file) => {
+                        let text = std::fs::read_to_string(file).map_err(|error|format!("{file}: {error}"))?;
+                        Weights::parse(&text).map_err(|error|format!("{file}: {error}"))?
+                    },
+                    None => weights,
+                };
+                Box::new(Ai::new(Engine::new(weights, 0.0, seat as u64 + 1), Some(book.clone()), limits))
+            },
+            Kind::Engine(command) => {
+                let mut engine = ExternalEngine::spawn(command)?;
+                engine.new_game()?;
+                Box::new(External::new(engine, limits))
+            },
+            Kind::Network(address) => Box::new(Remote::connect(address, local[0] as u8, seat as u8)?),
+        };
+        started.push(Seat { name: name.clone(), controller });
+    }
+    Ok(started)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::logic::Weights;
+    #[cfg(feature = "window")]
+    use crate::logic::Decision;
+    #[cfg(feature = "window")]
+    use crate::net::Client;
+    #[cfg(feature = "window")]
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    #[cfg(feature = "window")]
+    fn players_files(){
+        let text = "# who plays\nalice = human\n\nbot = ai tuned.txt  # stronger\nother = engine bin/engine --fast\nfar=network localhost:7878\nlazy = ai\n";
+        let seats = parse_seats(text).unwrap();
+        assert_eq!(seats, vec![
+            ("alice".to_string(), Kind::Human),
+            ("bot".to_string(), Kind::Ai(Some("tuned.txt".to_string()))),
+            ("other".to_string(), Kind::Engine("bin/engine --fast".to_string())),
+            ("far".to_string(), Kind::Network("localhost:7878".to_string())),
+            ("lazy".to_string(), Kind::Ai(None)),
+        ]);
+        assert!(parse_seats("alice human").unwrap_err().starts_with("line 1:"));
+        assert!(parse_seats("alice = human\nbob = engine").unwrap_err().starts_with("line 2:"));
+        assert!(parse_seats(" = ai").is_err());
+
+        let both_far = [("a".to_string(), Kind::Network("x:1".to_string())), ("b".to_string(), Kind::Network("x:1".to_string()))];
+        assert!(start_seats(&both_far, Weights::default(), &Arc::new(Book::default()), Limits::default()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "window")]
+    fn humans_and_bots_take_turns(){
+        let mut seats = [
+            Seat { name: "me".to_string(), controller: Box::new(Human) },
+            Seat { name: "bot".to_string(), controller: Box::new(Ai::new(Engine::new(Weights::default(), 0.0, 1), None, Limits::default())) },
+        ];
+        let game = Game::new(2, None);
+        let mut entered = None;
+        assert_eq!(seats[0].controller.poll_turn(&game, &mut entered), Ok(None));
+        // the bot ignores what is entered in the window, the human takes it
+        entered = Some(Vec::new());
+        let turn = wait_for(seats[1].controller.as_mut(), &game);
+        assert!(matches!(turn.as_slice(), [Action::CreateHomeworld { .. }]));
+        assert!(entered.is_some());
+        assert_eq!(seats[0].controller.poll_turn(&game, &mut entered), Ok(Some(Vec::new())));
+        assert_eq!(entered, None);
+    }
+
+    #[test]
+    fn the_engine_thinks_on_its_own_thread(){
+        let mut ai = Ai::new(Engine::new(Weights::default(), 0.0, 1), None, Limits { time: Some(Duration::from_secs(5)), ..Limits::default() });
+        let mut game = Game::new(2, None);
+        for _ in 0..2{
+            let turn = wait_for(&mut ai, &game);
+            for action in turn{
+                game.apply_action(action).unwrap();
+            }
+            game.end_turn();
+        }
+
+        // the search is stopped as soon as the position changes under it
+        let start = Instant::now();
+        assert_eq!(ai.poll_turn(&game, &mut None), Ok(None));
+        let elsewhere = Game::new(2, None);
+        // without waiting for the old search
+        let switched = Instant::now();
+        assert_eq!(ai.poll_turn(&elsewhere, &mut None), Ok(None));
+        assert!(switched.elapsed() < Duration::from_millis(50));
+        let turn = wait_for(&mut ai, &elsewhere);
+        assert!(matches!(turn.as_slice(), [Action::CreateHomeworld { .. }]));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        #[cfg(feature = "window")]
+        assert!(ai.progress().is_none());
+    }
+
+    /// polls `player` until it has a turn
+    fn wait_for(player: &mut dyn PlayerController, game: &Game)->Vec<Action>{
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10){
+            if let Some(turn) = player.poll_turn(game, &mut None).unwrap() {
+                return turn;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no turn came in");
+    }
+
+    #[test]
+    #[cfg(feature = "window")]
+    fn network_peers_relay_turns(){
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move||crate::net::serve(listener, 2, None));
+
+        // we play seat 0 here, the other side joins seat 1 with a plain client
+        let mut remote = Remote::connect(&address, 0, 1).unwrap();
+        let mut other = Client::join(&address, 1).unwrap();
+        let mut game = Game::new(2, None);
+        let mut bots = [Engine::new(Weights::default(), 0.0, 1), Engine::new(Weights::default(), 0.0, 2)];
+
+        let ours = bots[0].search(game.board(), None, Limits::default()).turn;
+        for action in &ours{
+            game.apply_action(*action).unwrap();
+        }
+        game.end_turn();
+        remote.observe(&game, &Played::Turn(0, ours.clone())).unwrap();
+
+        // the other side sees our turn and answers
+        let start = Instant::now();
+        while other.game.board() != game.board() {
+            assert!(start.elapsed() < Duration::from_secs(5), "our turn never arrived");
+            other.receive(Duration::from_millis(50));
+        }
+        let theirs = bots[1].search(game.board(), None, Limits::default()).turn;
+        other.play(&theirs).unwrap();
+        assert_eq!(wait_for(&mut remote, &game), theirs);
+
+        // what the server refuses comes back to us
+        remote.observe(&game, &Played::Turn(0, ours.clone())).unwrap();
+        let poll = |remote: &mut Remote|{
+            let start = Instant::now();
+            loop{
+                match remote.poll_decisions(){
+                    Ok(decisions) if decisions.is_empty() && start.elapsed() < Duration::from_secs(5) => {},
+                    result => return result,
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        let error = poll(&mut remote).unwrap_err();
+        assert!(error.starts_with("server: illegal turn homeworld"), "{error}");
+
+        other.decide(Decision::Resign).unwrap();
+        assert_eq!(poll(&mut remote), Ok(vec![Decision::Resign]));
+    }
+}
@@ -0,0 +1,535 @@
+//! a line based protocol between a host and an engine, modeled on UCI.
+//!
+//! The host writes commands to the engine's stdin, the engine answers on stdout:
+//!
+//! ```text
+//! hwp                               engine: id name ..., option lines, hwpok
+//! isready                           engine: readyok
+//! setoption name NAME value VALUE
+//! newgame
+//! position players N [pieces SET] [turns TURN | TURN | ...]
+//! go [movetime MS] [turns N] [depth D]   engine: info lines, bestmove TURN
+//! stop
+//! quit
+//! ```
+//!
+//! Turns are written in the game notation, one record line each, see `logic::format_turn`.
+//! Games with the standard pieces leave out `pieces`, see `logic::PieceSet`.
+//! `bestmove none` means the game is over. `stop` ends the search early, the
+//! engine answers with the best turn found so far. While it searches the engine
+//! answers `isready` at once, other commands wait for the search to end.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread::{JoinHandle, Scope, ScopedJoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::logic::{self, Action, Board, Book, Engine, Game, Limits, PieceSet, Record, Tablebase, Weights};
+
+/// how late an engine may answer: past the time it was given for a turn, or
+/// at all for anything else
+const ANSWER_TIME: Duration = Duration::from_secs(2);
+/// how long an engine may think about a turn without a time limit
+const THINKING_TIME: Duration = Duration::from_secs(60);
+
+/// the `position` command for the game so far
+fn position_command(game: &Game)->String{
+    let record = game.record();
+    let mut command = format!("position players {}", record.player_count);
+    if record.set != PieceSet::STANDARD {
+        command += &format!(" pieces {}", record.set);
+    }
+    if !record.turns.is_empty() {
+        let turns: Vec<String> = record.turns.iter().map(|turn|logic::format_turn(turn)).collect();
+        command += &format!(" turns {}", turns.join(" | "));
+    }
+    command
+}
+
+/// the game a `position` command sets up, from the words after `position`
+fn parse_position(args: &str)->Option<Game>{
+    let (players, turns) = match args.split_once(" turns "){
+        Some((players, turns)) => (players, Some(turns)),
+        None => (args, None),
+    };
+    let (players, set) = match players.split_once(" pieces "){
+        Some((players, set)) => (players, set.trim().parse().ok()?),
+        None => (players, PieceSet::STANDARD),
+    };
+    let player_count = players.trim().strip_prefix("players ")?.trim().parse().ok().filter(|count|(2..=4).contains(count))?;
+    let turns = match turns{
+        Some(turns) => turns.split('|').map(logic::parse_turn).collect::<Option<_>>()?,
+        None => Vec::new(),
+    };
+    Game::replay(&Record { player_count, set, result: None, turns, events: Vec::new() }, None)
+}
+
+/// the limits of a `go` command, from the words after `go`
+fn parse_go(args: &str)->Option<Limits>{
+    let mut limits = Limits::default();
+    let mut words = args.split_whitespace();
+    while let Some(word) = words.next(){
+        match word{
+            "movetime" => limits.time = Some(Duration::from_millis(words.next()?.parse().ok()?)),
+            "turns" => limits.turns = Some(words.next()?.parse().ok()?),
+            "depth" => limits.depth = Some(words.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(limits)
+}
+
+/// the output shared by the command loop and the search
+fn lock<W>(output: &Mutex<W>)->MutexGuard<'_, W>{
+    output.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// a search started by `go`, it writes its answer when it ends
+struct Running<'scope>{
+    stop: Arc<AtomicBool>,
+    /// gives the engine back
+    thread: ScopedJoinHandle<'scope, io::Result<Engine>>,
+}
+
+/// the built-in engine's side of the protocol
+struct Driver<'scope>{
+    engine: Engine,
+    book: Option<Arc<Book>>,
+    game: Game,
+    search: Option<Running<'scope>>,
+}
+
+impl<'scope> Driver<'scope>{
+    fn new()->Self{
+        Driver { engine: Engine::new(Weights::default(), 0.0, 1), book: None, game: Game::new(2, None), search: None }
+    }
+
+    /// waits for the search to answer, if there is one
+    fn wait(&mut self)->io::Result<()>{
+        if let Some(search) = self.search.take() {
+            self.engine = search.thread.join().map_err(|_|io::Error::other("the search crashed"))??;
+        }
+        Ok(())
+    }
+
+    /// searches the position on a thread of its own, which writes the answer to `output`
+    fn go<W: Write + Send>(&mut self, limits: Limits, output: &'scope Mutex<W>, scope: &'scope Scope<'scope, '_>){
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut engine = self.engine.clone();
+        engine.stop = Some(stop.clone());
+        let (board, book, stopped) = (self.game.board().clone(), self.book.clone(), stop.clone());
+        let thread = scope.spawn(move||{
+            let start = Instant::now();
+            let search = engine.search_with(&board, book.as_deref(), limits, |_|!stopped.load(Ordering::Relaxed));
+            let source = if search.book { " book" } else if search.solved { " tablebase" } else { "" };
+            let pv: Vec<String> = search.pv.iter().map(|turn|logic::format_turn(turn)).collect();
+            let mut output = lock(output);
+            writeln!(output, "info depth {} nodes {} turns {} score {:.2} time {}{source} pv {}",
+                search.depth, search.nodes, search.scored, search.score, start.elapsed().as_millis(), pv.join(" | "))?;
+            writeln!(output, "bestmove {}", logic::format_turn(&search.turn))?;
+            output.flush()?;
+            Ok(engine)
+        });
+        self.search = Some(Running { stop, thread });
+    }
+
+    fn set_option(&mut self, name: &str, value: &str)->Result<(), String>{
+        match name.to_lowercase().as_str(){
+            "weights" if value == "default" => self.engine.weights = Weights::default(),
+            "weights" => {
+                let text = std::fs::read_to_string(value).map_err(|error|format!("{value}: {error}"))?;
+                self.engine.weights = Weights::parse(&text).map_err(|error|format!("{value}: {error}"))?;
+            },
+            "noise" => self.engine.noise = value.parse().map_err(|_|"noise needs a number")?,
+            "seed" => {
+                let seed = value.parse().map_err(|_|"seed needs a number")?;
+                let tablebase = self.engine.tablebase.take();
+                self.engine = Engine::new(self.engine.weights, self.engine.noise, seed);
+                self.engine.tablebase = tablebase;
+            },
+            "book" if value.is_empty() => self.book = None,
+            "book" => self.book = Some(Arc::new(Book::from_dir(value).map_err(|error|format!("{value}: {error}"))?.0)),
+            "tablebase" if value.is_empty() => self.engine.tablebase = None,
+            "tablebase" => {
+                let bytes = std::fs::read(value).map_err(|error|format!("{value}: {error}"))?;
+                let tablebase = Tablebase::from_bytes(&bytes).map_err(|error|format!("{value}: {error}"))?;
+                self.engine.tablebase = Some(Arc::new(tablebase));
+            },
+            _ => return Err(format!("unknown option {name}")),
+        }
+        Ok(())
+    }
+
+    /// handles one command, false once the engine should quit
+    fn command<W: Write + Send>(&mut self, line: &str, shared: &'scope Mutex<W>, scope: &'scope Scope<'scope, '_>)->io::Result<bool>{
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command{
+            "stop" | "quit" => {
+                if let Some(search) = &self.search {
+                    search.stop.store(true, Ordering::Relaxed);
+                }
+                self.wait()?;
+                return Ok(command == "stop");
+            },
+            // answered while searching
+            "" | "isready" => {},
+            _ => self.wait()?,
+        }
+        let mut output = lock(shared);
+        match command{
+            "" | "newgame" => {},
+            "hwp" => {
+                writeln!(output, "id name live")?;
+                writeln!(output, "option name weights type string default default")?;
+                writeln!(output, "option name noise type float default 0")?;
+                writeln!(output, "option name seed type int default 1")?;
+                writeln!(output, "option name book type string default")?;
+                writeln!(output, "option name tablebase type string default")?;
+                writeln!(output, "hwpok")?;
+            },
+            "isready" => writeln!(output, "readyok")?,
+            "setoption" => {
+                let option = args.strip_prefix("name ").map(|option|match option.split_once(" value "){
+                    Some((name, value)) => (name.trim(), value.trim()),
+                    None => (option.trim(), ""),
+                });
+                let result = option.ok_or("setoption needs a name".to_string())
+                    .and_then(|(name, value)|self.set_option(name, value));
+                if let Err(error) = result {
+                    writeln!(output, "info string {error}")?;
+                }
+            },
+            "position" => match parse_position(args){
+                Some(game) => self.game = game,
+                None => writeln!(output, "info string cannot set up position {args}")?,
+            },
+            "go" => {
+                let Some(limits) = parse_go(args) else {
+                    writeln!(output, "info string cannot read go {args}")?;
+                    return Ok(true);
+                };
+                if self.game.outcome().is_some() {
+                    writeln!(output, "bestmove none")?;
+                    return Ok(true);
+                }
+                self.go(limits, shared, scope);
+            },
+            _ => writeln!(output, "info string unknown command {command}")?,
+        }
+        output.flush()?;
+        Ok(true)
+    }
+}
+
+/// answers protocol commands from `input` with the built-in engine until `quit`
+/// or the end of the input, where it waits for the search to answer
+pub fn run_engine(input: impl BufRead, output: impl Write + Send)->io::Result<()>{
+    let output = Mutex::new(output);
+    std::thread::scope(|scope|{
+        let mut driver = Driver::new();
+        for line in input.lines(){
+            if !driver.command(&line?, &output, scope)? {
+                break;
+            }
+        }
+        driver.wait()
+    })
+}
+
+/// an engine in another process that plays through the protocol
+pub(crate) struct ExternalEngine{
+    child: Child,
+    input: ChildStdin,
+    /// the engine's output lines, read on a thread of their own so waiting
+    /// for an answer can give up
+    output: Receiver<String>,
+    reader: Option<JoinHandle<()>>,
+    /// what the engine calls itself
+    pub(crate) name: String,
+    /// the position asked about with `go` and when the answer is due
+    asked: Option<(Board, Instant)>,
+    /// answers still to come for positions that went away
+    stale: usize,
+}
+
+impl ExternalEngine{
+    /// starts `command`, a program followed by its arguments, and waits for the handshake
+    pub(crate) fn spawn(command: &str)->Result<Self, String>{
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("no engine command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error|format!("{program}: {error}"))?;
+        let input = child.stdin.take().ok_or("no engine stdin")?;
+        let stdout = BufReader::new(child.stdout.take().ok_or("no engine stdout")?);
+        let (lines, output) = mpsc::channel();
+        // ends when the engine closes its output, at the latest once it is killed
+        let reader = std::thread::spawn(move||{
+            for line in stdout.lines().map_while(Result::ok){
+                if lines.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = ExternalEngine {
+            child,
+            input,
+            output,
+            reader: Some(reader),
+            name: program.to_string(),
+            asked: None,
+            stale: 0,
+        };
+        engine.send("hwp")?;
+        let due = Instant::now() + ANSWER_TIME;
+        loop{
+            let line = engine.receive(due)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+            if line.trim() == "hwpok" {
+                break;
+            }
+        }
+        Ok(engine)
+    }
+
+    fn send(&mut self, line: &str)->Result<(), String>{
+        writeln!(self.input, "{line}")
+            .and_then(|_|self.input.flush())
+            .map_err(|error|format!("{}: {error}", self.name))
+    }
+
+    /// the next line from the engine, an error once it has quit or if nothing came by `due`
+    fn receive(&mut self, due: Instant)->Result<String, String>{
+        match self.output.recv_timeout(due.saturating_duration_since(Instant::now())){
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(format!("{} didn't answer in time", self.name)),
+            Err(RecvTimeoutError::Disconnected) => Err(format!("{} quit", self.name)),
+        }
+    }
+
+    /// waits until the engine has handled everything sent so far
+    fn sync(&mut self)->Result<(), String>{
+        self.send("isready")?;
+        let due = Instant::now() + ANSWER_TIME;
+        while self.receive(due)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub(crate) fn set_option(&mut self, name: &str, value: &str)->Result<(), String>{
+        self.send(&format!("setoption name {name} value {value}"))?;
+        self.sync()
+    }
+
+    pub(crate) fn new_game(&mut self)->Result<(), String>{
+        self.send("newgame")?;
+        self.sync()
+    }
+
+    /// the position the engine is thinking about, until `answer` has its turn
+    pub(crate) fn asked(&self)->Option<&Board>{
+        self.asked.as_ref().map(|(board, _)|board)
+    }
+
+    /// asks for the turn to play in `game`, see `answer`. The engine is told
+    /// to stop thinking about the position asked about before.
+    pub(crate) fn ask(&mut self, game: &Game, limits: Limits)->Result<(), String>{
+        if self.asked.take().is_some() {
+            self.send("stop")?;
+            self.stale += 1;
+        }
+        self.send(&position_command(game))?;
+        let mut go = "go".to_string();
+        if let Some(time) = limits.time {
+            go += &format!(" movetime {}", time.as_millis());
+        }
+        if let Some(turns) = limits.turns {
+            go += &format!(" turns {turns}");
+        }
+        if let Some(depth) = limits.depth {
+            go += &format!(" depth {depth}");
+        }
+        self.send(&go)?;
+        let due = Instant::now() + limits.time.unwrap_or(THINKING_TIME) + ANSWER_TIME;
+        self.asked = Some((game.board().clone(), due));
+        Ok(())
+    }
+
+    /// the turn asked for, none while the engine is still thinking. An error if
+    /// the engine fails, doesn't answer in time or answers with a turn that
+    /// can't be played.
+    pub(crate) fn answer(&mut self)->Result<Option<Vec<Action>>, String>{
+        let Some((_, due)) = self.asked else {
+            return Ok(None);
+        };
+        let turn = loop{
+            match self.output.try_recv(){
+                Ok(line) => match line.strip_prefix("bestmove "){
+                    Some(_) if self.stale > 0 => self.stale -= 1,
+                    Some(turn) => break turn.trim().to_string(),
+                    None => {},
+                },
+                Err(TryRecvError::Empty) if Instant::now() < due => return Ok(None),
+                Err(TryRecvError::Empty) => return Err(format!("{} didn't answer in time", self.name)),
+                Err(TryRecvError::Disconnected) => return Err(format!("{} quit", self.name)),
+            }
+        };
+        let (board, _) = self.asked.take().expect("checked above");
+        let actions = logic::parse_turn(&turn).ok_or(format!("{} played unreadable turn {turn}", self.name))?;
+        match board.legal_turn(&actions){
+            Some(_) => Ok(Some(actions)),
+            None => Err(format!("{} played illegal turn {turn}", self.name)),
+        }
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // give it a moment to exit on its own
+        for _ in 0..20{
+            if let Ok(Some(_)) = self.child.try_wait() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn talk(commands: &str)->Vec<String>{
+        let mut output = Vec::new();
+        run_engine(commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(|line|line.to_string()).collect()
+    }
+
+    #[test]
+    fn handshake_and_options(){
+        let lines = talk("hwp\nsetoption name noise value 2\nsetoption name colour value red\nisready\n");
+        assert_eq!(lines.first().map(String::as_str), Some("id name live"));
+        assert!(lines.contains(&"hwpok".to_string()));
+        assert_eq!(lines.iter().filter(|line|line.starts_with("info string unknown option")).count(), 1);
+        assert_eq!(lines.last().map(String::as_str), Some("readyok"));
+    }
+
+    #[test]
+    fn positions_and_best_moves(){
+        let mut game = Game::new(2, None);
+        let lines = talk("position players 2\ngo\nquit\ngo\n");
+        let turn = lines.last().and_then(|line|line.strip_prefix("bestmove ")).unwrap();
+        for action in logic::parse_turn(turn).unwrap(){
+            game.apply_action(action).unwrap();
+        }
+        game.end_turn();
+
+        // the host's position command sets up the same game on the engine's side
+        let command = position_command(&game);
+        assert!(command.starts_with("position players 2 turns homeworld "));
+        let replayed = parse_position(command.strip_prefix("position ").unwrap()).unwrap();
+        assert_eq!(replayed.board(), game.board());
+
+        let lines = talk(&format!("{command}\ngo turns 3 movetime 1000\n"));
+        assert!(lines[0].starts_with("info depth 1 nodes "));
+        let turn = lines[1].strip_prefix("bestmove ").unwrap();
+        let reply = logic::parse_turn(turn).unwrap();
+        assert!(matches!(reply.as_slice(), [Action::CreateHomeworld { .. }]));
+        assert!(game.board().clone().apply_action(reply[0]).is_some());
+
+        // reduced piece sets travel with the position
+        let set: PieceSet = "gy/13/2".parse().unwrap();
+        let game = Game::from_board(logic::Board::with_set(2, set), None);
+        let command = position_command(&game);
+        assert_eq!(command, "position players 2 pieces gy/13/2");
+        assert_eq!(parse_position(command.strip_prefix("position ").unwrap()).unwrap().board(), game.board());
+
+        assert_eq!(talk("position players 2 turns nonsense\n"), vec!["info string cannot set up position players 2 turns nonsense"]);
+        assert!(parse_position("players 1").is_none());
+        assert!(parse_position("players 5").is_none());
+        assert_eq!(parse_go("movetime 20"), Some(Limits { time: Some(Duration::from_millis(20)), ..Limits::default() }));
+        assert_eq!(parse_go("depth 2 turns 5"), Some(Limits { turns: Some(5), depth: Some(2), ..Limits::default() }));
+        assert_eq!(parse_go("mate 3"), None);
+    }
+
+    #[test]
+    fn stop_ends_the_search(){
+        let mut game = Game::new(2, None);
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        for _ in 0..2{
+            for action in engine.search(game.board(), None, Limits::default()).turn{
+                game.apply_action(action).unwrap();
+            }
+            game.end_turn();
+        }
+        // too deep to finish, but stopped right away
+        let start = Instant::now();
+        let lines = talk(&format!("{}\ngo depth 6\nisready\nstop\n", position_command(&game)));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(lines[0], "readyok");
+        let depth: u8 = lines[1].strip_prefix("info depth ").and_then(|info|info.split(' ').next()?.parse().ok()).unwrap();
+        assert!(depth < 6, "{}", lines[1]);
+        let turn = logic::parse_turn(lines[2].strip_prefix("bestmove ").unwrap()).unwrap();
+        assert!(game.board().legal_turn(&turn).is_some());
+    }
+
+    /// starts a shell script as an engine that answers `go` with `bestmove`
+    fn script(name: &str, bestmove: &str)->ExternalEngine{
+        let path = std::env::temp_dir().join(format!("{name}-{}.sh", std::process::id()));
+        let script = format!("while read line; do\n  case \"$line\" in\n    hwp) echo 'id name {name}'; echo hwpok;;\n    isready) echo readyok;;\n    go*) {bestmove};;\n  esac\ndone\n");
+        std::fs::write(&path, script).unwrap();
+        let engine = ExternalEngine::spawn(&format!("sh {}", path.display())).unwrap();
+        // the shell read the whole loop before it answered the handshake
+        std::fs::remove_file(&path).unwrap();
+        engine
+    }
+
+    #[test]
+    fn external_engines_answer_in_time(){
+        let game = Game::new(2, None);
+        let turn = Engine::new(Weights::default(), 0.0, 1).search(game.board(), None, Limits::default()).turn;
+        let limits = Limits { time: Some(Duration::from_millis(10)), ..Limits::default() };
+
+        let mut engine = script("quick", &format!("echo 'bestmove {}'", logic::format_turn(&turn)));
+        assert_eq!(engine.name, "quick");
+        engine.new_game().unwrap();
+        engine.ask(&game, limits).unwrap();
+        assert_eq!(engine.asked(), Some(game.board()));
+        let start = Instant::now();
+        let answer = loop{
+            if let Some(answer) = engine.answer().unwrap() {
+                break answer;
+            }
+            assert!(start.elapsed() < ANSWER_TIME);
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(answer, turn);
+        assert_eq!(engine.asked(), None);
+
+        let mut engine = script("mute", "true");
+        engine.ask(&game, limits).unwrap();
+        let start = Instant::now();
+        let error = loop{
+            match engine.answer(){
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                result => break result.unwrap_err(),
+            }
+        };
+        assert_eq!(error, "mute didn't answer in time");
+        assert!(start.elapsed() >= ANSWER_TIME);
+    }
+}
@@ -0,0 +1,82 @@
+//! solves reduced piece sets by retrograde analysis, see `src/bin/tablebase.rs`
+
+use std::time::Duration;
+
+use crate::logic::{Board, Engine, Limits, PieceSet, Tablebase, Value, Weights};
+
+const USAGE: &str = "\
+usage: tablebase [options] PIECES
+  PIECES        the piece set as colors, sizes and copies, like `rgby/13/1`
+  --max N       give up beyond N positions (default 2000000)
+  --out FILE    write the solved table to FILE
+  --load FILE   read a table written with --out instead of solving
+  --verify N    compare the engine's turns to the best ones in N positions
+  --movetime MS the engine's time per turn when verifying (default no limit)";
+
+struct Options{
+    set: Option<PieceSet>,
+    max: usize,
+    out: Option<String>,
+    load: Option<String>,
+    verify: usize,
+    limits: Limits,
+}
+
+fn parse_options(args: &[String])->Result<Options, String>{
+    let mut options = Options { set: None, max: 2_000_000, out: None, load: None, verify: 0, limits: Limits::default() };
+    let mut args = args.iter();
+    while let Some(arg) = args.next(){
+        let mut value = ||args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str(){
+            "--max" => options.max = value()?.parse().map_err(|_|"--max needs a number")?,
+            "--out" => options.out = Some(value()?.clone()),
+            "--load" => options.load = Some(value()?.clone()),
+            "--verify" => options.verify = value()?.parse().map_err(|_|"--verify needs a number")?,
+            "--movetime" => {
+                let millis = value()?.parse().map_err(|_|"--movetime needs a number")?;
+                options.limits.time = Some(Duration::from_millis(millis));
+            },
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n{USAGE}")),
+            _ => options.set = Some(arg.parse()?),
+        }
+    }
+    if options.set.is_none() && options.load.is_none() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+/// solves or loads the table for command line `args`, without the program name
+pub fn run(args: &[String])->Result<(), String>{
+    let options = parse_options(args)?;
+    let table = match (&options.load, options.set){
+        (Some(path), set) => {
+            let bytes = std::fs::read(path).map_err(|error|format!("{path}: {error}"))?;
+            let table = Tablebase::from_bytes(&bytes).map_err(|error|format!("{path}: {error}"))?;
+            if set.is_some_and(|set|set != table.set()) {
+                return Err(format!("{path} holds {}", table.set()));
+            }
+            table
+        },
+        (None, Some(set)) => Tablebase::solve(set, options.max)?,
+        (None, None) => unreachable!("checked in parse_options"),
+    };
+
+    let (wins, losses, draws) = table.counts();
+    println!("{}: {} positions, {wins} won, {losses} lost and {draws} drawn for the player to move",
+        table.set(), wins + losses + draws);
+    let start = Board::with_set(2, table.set());
+    println!("from the empty board: {}", table.probe(&start).unwrap_or(Value::Draw));
+    if let Some(path) = &options.out {
+        std::fs::write(path, table.to_bytes()).map_err(|error|format!("{path}: {error}"))?;
+    }
+
+    if options.verify > 0 {
+        let mut engine = Engine::new(Weights::default(), 0.0, 1);
+        let verification = table.verify(&mut engine, options.limits, options.verify);
+        println!("the engine in {} positions: {} best, {} slower and {} worse",
+            verification.positions, verification.best, verification.slower, verification.worse);
+    }
+    Ok(())
+}
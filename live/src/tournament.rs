@@ -0,0 +1,308 @@
+//! headless round robin between engine configurations, see `src/bin/tournament.rs`
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::logic::{Book, Decision, Engine, Game, Limits, Rng, Weights, format_turn};
+use crate::players::{Ai, External, Played, PlayerController};
+use crate::protocol::ExternalEngine;
+
+const USAGE: &str = "\
+usage: tournament [options] CONFIG CONFIG...
+  CONFIG        a weights file like eval_weights.txt, `default`, or
+                `engine:COMMAND` for an engine that speaks the protocol in protocol.rs
+  --games N     games per pairing, both sides start half of them (default 20)
+  --noise X     random bonus per turn so games vary (default 0.5)
+  --seed N      seed for the first game (default 1)
+  --movetime MS time per turn (default no limit)
+  --book DIR    play book moves compiled from the games in DIR
+  --out DIR     write every game record to DIR";
+
+/// games that go on this long are agreed drawn
+const MAX_TURNS: usize = 300;
+/// turns without progress before the game is a draw, as in the window
+const NO_PROGRESS_LIMIT: u32 = 50;
+
+struct Options{
+    games: u32,
+    noise: f32,
+    seed: u64,
+    limits: Limits,
+    book: Option<String>,
+    out: Option<String>,
+    configs: Vec<String>,
+}
+
+fn parse_options(args: &[String])->Result<Options, String>{
+    let mut options = Options { games: 20, noise: 0.5, seed: 1, limits: Limits::default(), book: None, out: None, configs: Vec::new() };
+    let mut args = args.iter();
+    while let Some(arg) = args.next(){
+        let mut value = ||args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str(){
+            "--games" => options.games = value()?.parse().map_err(|_|"--games needs a number")?,
+            "--noise" => options.noise = value()?.parse().map_err(|_|"--noise needs a number")?,
+            "--seed" => options.seed = value()?.parse().map_err(|_|"--seed needs a number")?,
+            "--movetime" => {
+                let millis = value()?.parse().map_err(|_|"--movetime needs a number")?;
+                options.limits.time = Some(Duration::from_millis(millis));
+            },
+            "--book" => options.book = Some(value()?.clone()),
+            "--out" => options.out = Some(value()?.clone()),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n{USAGE}")),
+            _ => options.configs.push(arg.clone()),
+        }
+    }
+    if options.configs.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+/// who plays for a config
+#[derive(Debug, Clone)]
+enum Config{
+    Builtin(Weights),
+    /// the command that starts the engine
+    External(String),
+}
+
+fn load_config(config: &str)->Result<Config, String>{
+    if let Some(command) = config.strip_prefix("engine:") {
+        return Ok(Config::External(command.to_string()));
+    }
+    if config == "default" {
+        return Ok(Config::Builtin(Weights::default()));
+    }
+    let text = std::fs::read_to_string(config).map_err(|error|format!("{config}: {error}"))?;
+    Weights::parse(&text).map(Config::Builtin).map_err(|error|format!("{config}: {error}"))
+}
+
+/// the file name without directories and extension
+fn config_name(config: &str)->&str{
+    let config = config.strip_prefix("engine:").map(|command|command.split_whitespace().next().unwrap_or(command)).unwrap_or(config);
+    let name = config.rsplit(['/', '\\']).next().unwrap_or(config);
+    name.split('.').next().filter(|name|!name.is_empty()).unwrap_or(name)
+}
+
+/// wins, draws and losses of one side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Score{
+    pub(crate) wins: u32,
+    pub(crate) draws: u32,
+    pub(crate) losses: u32,
+}
+
+impl Score{
+    fn games(self)->u32{
+        self.wins + self.draws + self.losses
+    }
+    fn add(&mut self, other: Score){
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+    fn flipped(self)->Score{
+        Score { wins: self.losses, draws: self.draws, losses: self.wins }
+    }
+    /// points per game, a draw is half a point
+    pub(crate) fn mean(self)->f64{
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+    /// Elo difference to the opponents with a 95% confidence interval, from the
+    /// normal approximation of the mean score. Perfect scores are treated as
+    /// half a game short of perfect so the numbers stay finite.
+    pub(crate) fn elo(self)->(f64, f64, f64){
+        let n = self.games() as f64;
+        let mean = self.mean();
+        let variance = (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2)) / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        let elo = |score: f64|{
+            let score = score.clamp(0.5 / n, 1.0 - 0.5 / n);
+            -400.0 * (1.0 / score - 1.0).log10()
+        };
+        (elo(mean), elo(mean - margin), elo(mean + margin))
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (elo, low, high) = self.elo();
+        write!(f, "+{} ={} -{}  {:5.1}%  elo {elo:+6.0} [{low:+.0}, {high:+.0}]",
+            self.wins, self.draws, self.losses, self.mean() * 100.0)
+    }
+}
+
+/// a fresh player for one game
+fn player(config: &Config, noise: f32, seed: u64, book: Option<&Arc<Book>>, limits: Limits)->Result<Box<dyn PlayerController>, String>{
+    match config{
+        Config::Builtin(weights) => Ok(Box::new(Ai::new(Engine::new(*weights, noise, seed), book.cloned(), limits))),
+        Config::External(command) => {
+            let mut engine = ExternalEngine::spawn(command)?;
+            engine.set_option("seed", &seed.to_string())?;
+            engine.new_game()?;
+            Ok(Box::new(External::new(engine, limits)))
+        },
+    }
+}
+
+/// plays one game, `players[0]` moves first. The winner is none for a draw.
+/// A player that fails or makes an illegal turn resigns.
+fn play(mut players: [&mut dyn PlayerController; 2])->Game{
+    let mut game = Game::new(2, Some(NO_PROGRESS_LIMIT));
+    for _ in 0..MAX_TURNS{
+        if game.outcome().is_some() {
+            return game;
+        }
+        let player = game.board().current_player();
+        let turn = loop{
+            match players[player as usize].poll_turn(&game, &mut None){
+                Ok(Some(turn)) => break turn,
+                // the built-in engine thinks on a thread of its own
+                Ok(None) => std::thread::sleep(Duration::from_millis(1)),
+                Err(error) => {
+                    eprintln!("{error}");
+                    game.decide(player, Decision::Resign);
+                    return game;
+                },
+            }
+        };
+        if game.board().legal_turn(&turn).is_none() {
+            eprintln!("illegal turn {}", format_turn(&turn));
+            game.decide(player, Decision::Resign);
+            return game;
+        }
+        for action in &turn{
+            game.apply_action(*action).expect("the turn was checked");
+        }
+        game.end_turn();
+        for other in players.iter_mut(){
+            if let Err(error) = other.observe(&game, &Played::Turn(player, turn.clone())) {
+                eprintln!("{error}");
+            }
+        }
+    }
+    if game.outcome().is_none() {
+        game.decide(0, Decision::OfferDraw);
+        game.decide(1, Decision::AcceptDraw);
+    }
+    game
+}
+
+/// runs the tournament for command line `args`, without the program name
+pub fn run(args: &[String])->Result<(), String>{
+    let options = parse_options(args)?;
+    let configs = options.configs.iter().map(|config|load_config(config)).collect::<Result<Vec<_>, _>>()?;
+    let names: Vec<&str> = options.configs.iter().map(|config|config_name(config)).collect();
+    let book = match &options.book{
+        Some(dir) => Some(Arc::new(Book::from_dir(dir).map_err(|error|format!("{dir}: {error}"))?.0)),
+        None => None,
+    };
+    if let Some(out) = &options.out {
+        std::fs::create_dir_all(out).map_err(|error|format!("{out}: {error}"))?;
+    }
+
+    let count = configs.len();
+    let mut results = vec![vec![Score::default(); count]; count];
+    let mut seeds = Rng::new(options.seed);
+    for a in 0..count{
+        for b in a+1..count{
+            for round in 0..options.games{
+                let mut player_a = player(&configs[a], options.noise, seeds.below(u32::MAX as usize) as u64, book.as_ref(), options.limits)?;
+                let mut player_b = player(&configs[b], options.noise, seeds.below(u32::MAX as usize) as u64, book.as_ref(), options.limits)?;
+                let a_starts = round % 2 == 0;
+                let game = match a_starts{
+                    true => play([player_a.as_mut(), player_b.as_mut()]),
+                    false => play([player_b.as_mut(), player_a.as_mut()]),
+                };
+                let a_player = if a_starts { 0 } else { 1 };
+                let score = match game.outcome().and_then(|outcome|outcome.winner){
+                    None => Score { draws: 1, ..Default::default() },
+                    Some(winner) if winner == a_player => Score { wins: 1, ..Default::default() },
+                    Some(_) => Score { losses: 1, ..Default::default() },
+                };
+                results[a][b].add(score);
+                results[b][a].add(score.flipped());
+
+                if let Some(out) = &options.out {
+                    let (first, second) = if a_starts { (names[a], names[b]) } else { (names[b], names[a]) };
+                    let path = format!("{out}/{first}-vs-{second}-{round}.txt");
+                    std::fs::write(&path, game.record().narrated(&[first.to_string(), second.to_string()])).map_err(|error|format!("{path}: {error}"))?;
+                }
+                eprint!("\r{} vs {}: {}/{}", names[a], names[b], round + 1, options.games);
+            }
+            eprintln!();
+        }
+    }
+
+    println!("pairings, from the first side's point of view:");
+    for a in 0..count{
+        for b in a+1..count{
+            println!("  {} vs {}: {}", names[a], names[b], results[a][b]);
+        }
+    }
+    println!("against the field:");
+    for (a, name) in names.iter().enumerate(){
+        let mut total = Score::default();
+        for score in &results[a]{
+            total.add(*score);
+        }
+        println!("  {name}: {total}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::logic::Action;
+
+    #[test]
+    fn elo_estimates(){
+        let even = Score { wins: 10, draws: 10, losses: 10 };
+        let (elo, low, high) = even.elo();
+        assert_eq!(elo, 0.0);
+        assert!(low < 0.0 && high > 0.0);
+
+        let ahead = Score { wins: 3, draws: 0, losses: 1 };
+        assert!((ahead.elo().0 - 190.8).abs() < 0.1);
+        let (elo, low, high) = Score { wins: 30, draws: 0, losses: 10 }.elo();
+        assert!((elo - 190.8).abs() < 0.1);
+        // more games, narrower interval
+        assert!(high - low < ahead.elo().2 - ahead.elo().1);
+
+        let (elo, ..) = Score { wins: 5, draws: 0, losses: 0 }.elo();
+        assert!(elo.is_finite() && elo > 0.0);
+    }
+
+    #[test]
+    fn options_and_names(){
+        let args: Vec<String> = ["--games", "4", "a/b/aggressive.txt", "default", "--movetime", "50", "engine:bin/other --fast"].iter().map(|arg|arg.to_string()).collect();
+        let options = parse_options(&args).unwrap();
+        assert_eq!(options.games, 4);
+        assert_eq!(options.configs.len(), 3);
+        assert_eq!(options.limits.time, Some(Duration::from_millis(50)));
+        assert_eq!(config_name(&options.configs[0]), "aggressive");
+        assert_eq!(config_name(&options.configs[2]), "other");
+        assert!(parse_options(&args[..3]).is_err());
+        assert!(parse_options(&["--games".to_string()]).is_err());
+    }
+
+    /// passes every turn, which is never legal
+    struct Passer;
+
+    impl PlayerController for Passer {
+        fn poll_turn(&mut self, _game: &Game, _entered: &mut Option<Vec<Action>>)->Result<Option<Vec<Action>>, String>{
+            Ok(Some(Vec::new()))
+        }
+    }
+
+    #[test]
+    fn illegal_turns_forfeit(){
+        let mut ai = Ai::new(Engine::new(Weights::default(), 0.0, 1), None, Limits::default());
+        let game = play([&mut ai, &mut Passer]);
+        assert_eq!(game.outcome().unwrap().winner, Some(0));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::graphics::*;
+use crate::logic::{self, Board, Continuation, Id, Powers, RuleHelp, SystemId};
+use crate::Ui;
+
+fn initial(color: logic::Color)->char{
+    match color{
+        logic::Color::Red => 'r',
+        logic::Color::Green => 'g',
+        logic::Color::Blue => 'b',
+        logic::Color::Yellow => 'y',
+    }
+}
+
+fn draw_color(color: logic::Color)->Color{
+    match color{
+        logic::Color::Red => colors::RED,
+        logic::Color::Green => colors::GREEN,
+        logic::Color::Blue => colors::SKYBLUE,
+        logic::Color::Yellow => colors::YELLOW,
+    }
+}
+
+/// `r1`, `y3`, ...: color initial and size from 1 to 3, or 4 in sets with a fourth size
+pub(crate) fn short_name(id: Id)->String{
+    format!("{}{}", initial(id.color()), id.size() as u8 + 1)
+}
+
+fn piece_color(id: Id)->Color{
+    draw_color(id.color())
+}
+
+/// the initials of `powers` in their colors, starting at `pos`
+pub(crate) fn draw_powers(ui: &mut Ui, powers: Powers, pos: Pos2){
+    let mut pos = pos;
+    for color in powers.iter(){
+        ui.draw_text(&initial(color).to_string(), pos, draw_color(color));
+        pos.x += 12.0;
+    }
+}
+
+/// where every system is drawn, spread on a circle inside `area`
+pub(crate) fn layout(board: &Board, area: Rect)->Vec<(SystemId, Pos2)>{
+    let systems: Vec<SystemId> = board.systems().map(|(id, _)|id).collect();
+    let radius = area.size().smaller_comp() / 2.0 - SYSTEM_RADIUS;
+    systems.iter().enumerate().map(|(i, id)|{
+        let angle = std::f32::consts::TAU * i as f32 / systems.len() as f32;
+        (*id, area.center() + vec2(angle.sin(), -angle.cos()) * radius)
+    }).collect()
+}
+
+const SYSTEM_RADIUS: f32 = 40.0;
+
+/// where the `i`th ship of the `row`th line of ships goes in the system drawn
+/// at `pos`, the stars are on line 0
+fn ship_pos(pos: Pos2, row: usize, i: usize)->Pos2{
+    vec2(pos.x - 16.0 + 20.0 * i as f32, pos.y - SYSTEM_RADIUS + 8.0 + 16.0 * row as f32)
+}
+
+/// the system under `point` as laid out by `draw_board`, and the ship there if it is on one
+pub(crate) fn pick(board: &Board, area: Rect, point: Pos2)->Option<(SystemId, Option<Id>)>{
+    let (id, pos) = layout(board, area).into_iter().find(|(_, pos)|pos.distance(point) <= SYSTEM_RADIUS)?;
+    let system = board.system(id)?;
+    let rows = system.ships.iter().filter(|ships|!ships.is_empty());
+    for (row, ships) in rows.enumerate(){
+        for (i, ship) in ships.iter().enumerate(){
+            if Rect::from_min_size(ship_pos(pos, row + 1, i), vec2(20.0, 16.0)).contains(point) {
+                return Some((id, Some(*ship)));
+            }
+        }
+    }
+    Some((id, None))
+}
+
+/// systems with their stars and ships, lines between connected systems.
+/// The `selected` system or ship is outlined.
+pub(crate) fn draw_board(ui: &mut Ui, board: &Board, area: Rect, selected: Option<(SystemId, Option<Id>)>){
+    let layout = layout(board, area);
+    for (i, (a, a_pos)) in layout.iter().enumerate(){
+        for (b, b_pos) in &layout[i+1..]{
+            if board.connected(*a, *b) {
+                ui.draw_line(*a_pos, *b_pos, 1.0, colors::DARKGRAY);
+            }
+        }
+    }
+
+    for (id, pos) in layout{
+        let Some(system) = board.system(id) else {
+            continue;
+        };
+        ui.draw_circle(pos, SYSTEM_RADIUS, colors::BLACK);
+        let outline = match selected{
+            Some((system, None)) if system == id => colors::ORANGE,
+            _ if system.home.is_some() => colors::WHITE,
+            _ => colors::GRAY,
+        };
+        ui.draw_circle_lines(pos, SYSTEM_RADIUS, outline);
+
+        let y = pos.y - SYSTEM_RADIUS + 8.0;
+        let mut x = pos.x - 12.0 * system.stars.len() as f32;
+        for star in &system.stars{
+            ui.draw_text(&short_name(*star), vec2(x, y), piece_color(*star));
+            x += 24.0;
+        }
+        if let Some(player) = system.home {
+            ui.draw_text(&format!("home {player}"), vec2(pos.x - 24.0, pos.y + SYSTEM_RADIUS + 2.0), colors::GRAY);
+        }
+        let rows = system.ships.iter().enumerate().filter(|(_, ships)|!ships.is_empty());
+        for (row, (player, ships)) in rows.enumerate(){
+            let start = ship_pos(pos, row + 1, 0);
+            ui.draw_text(&format!("{player}:"), start - vec2(18.0, 0.0), colors::GRAY);
+            for (i, ship) in ships.iter().enumerate(){
+                let at = ship_pos(pos, row + 1, i);
+                ui.draw_text(&short_name(*ship), at, piece_color(*ship));
+                if selected == Some((id, Some(*ship))) {
+                    ui.draw_rect_lines(Rect::from_min_size(at - vec2(2.0, 1.0), vec2(20.0, 16.0)), 1.0, colors::ORANGE);
+                }
+            }
+        }
+    }
+}
+
+/// how far ahead player 0 is: their share fills the bar from the bottom,
+/// an even position fills half of it
+pub(crate) fn draw_eval_bar(ui: &mut Ui, advantage: f32, rect: Rect){
+    // about 10 points is a clearly won position
+    let share = 0.5 + 0.5 * (advantage / 10.0).tanh();
+    ui.draw_rect(rect, colors::DARKGRAY);
+    let height = rect.height() * share;
+    ui.draw_rect(Rect::new(rect.min.x, rect.max.y - height, rect.width(), height), colors::WHITE);
+    ui.draw_rect_lines(rect, 1.0, colors::GRAY);
+}
+
+/// book moves for the position on the board, with how often they were
+/// played and how well they scored for the player who played them
+pub(crate) fn draw_book(ui: &mut Ui, continuations: &[&Continuation], rect: Rect){
+    ui.draw_rect(rect, colors::BLACK);
+    ui.draw_rect_lines(rect, 1.0, colors::GRAY);
+    let mut pos = rect.min + vec2(10.0, 10.0);
+    if continuations.is_empty() {
+        ui.draw_text("no book moves here", pos, colors::GRAY);
+        return;
+    }
+    for continuation in continuations{
+        if pos.y > rect.max.y - 20.0 {
+            break;
+        }
+        let turn: Vec<String> = continuation.turn.iter().map(|action|action.to_string()).collect();
+        let turn = if turn.is_empty() { "pass".to_string() } else { turn.join("; ") };
+        let stats = format!("{:>4} games {:>4.0}%", continuation.games, continuation.win_rate() * 100.0);
+        ui.draw_text(&stats, pos, colors::WHITE);
+        ui.draw_text(&turn, pos + vec2(150.0, 0.0), colors::GRAY);
+        pos.y += 20.0;
+    }
+}
+
+/// a button per correspondence file and one to start a new game,
+/// `Some(None)` for the new game
+pub(crate) fn draw_files(ui: &mut Ui, files: &[String], rect: Rect)->Option<Option<usize>>{
+    ui.draw_rect(rect, colors::BLACK);
+    ui.draw_rect_lines(rect, 1.0, colors::GRAY);
+    let mut pos = rect.min + vec2(10.0, 10.0);
+    let size = vec2(rect.width() - 20.0, 24.0);
+    let mut clicked = None;
+    if ui.button(Rect::from_min_size(pos, size), "new correspondence game") {
+        clicked = Some(None);
+    }
+    for (i, file) in files.iter().enumerate(){
+        pos.y += 30.0;
+        if pos.y > rect.max.y - 30.0 {
+            break;
+        }
+        if ui.button(Rect::from_min_size(pos, size), file) {
+            clicked = Some(Some(i));
+        }
+    }
+    clicked
+}
+
+/// `text` broken between words into lines at most `width` wide
+fn wrap(ui: &Ui, text: &str, width: f32)->Vec<String>{
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace(){
+        match lines.last_mut(){
+            Some(line) if ui.measure(ui.font, &format!("{line} {word}"), ui.text_scale).x <= width => {
+                line.push(' ');
+                line.push_str(word);
+            },
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// the narrated turns, numbered, the latest at the bottom and as many
+/// before it as fit
+pub(crate) fn draw_log(ui: &mut Ui, log: &[String], rect: Rect){
+    ui.draw_rect(rect, colors::BLACK);
+    ui.draw_rect_lines(rect, 1.0, colors::GRAY);
+    let mut pos = rect.min + vec2(10.0, 10.0);
+    if log.is_empty() {
+        ui.draw_text("no turns played yet", pos, colors::GRAY);
+        return;
+    }
+    let width = rect.width() - 20.0;
+    let line_height = 18.0;
+    let mut lines = Vec::new();
+    for (i, turn) in log.iter().enumerate(){
+        let color = if i + 1 == log.len() { colors::WHITE } else { colors::GRAY };
+        lines.extend(wrap(ui, &format!("{}. {turn}", i + 1), width).into_iter().map(|line|(line, color)));
+    }
+    let fit = ((rect.height() - 20.0) / line_height) as usize;
+    for (line, color) in &lines[lines.len().saturating_sub(fit)..]{
+        ui.draw_text(line, pos, *color);
+        pos.y += line_height;
+    }
+}
+
+/// what the player to move can do with the selected system or ship, and
+/// for everything they can't, the reason
+pub(crate) fn draw_rules(ui: &mut Ui, selected: Option<Id>, help: &[RuleHelp], rect: Rect){
+    ui.draw_rect(rect, colors::BLACK);
+    ui.draw_rect_lines(rect, 1.0, colors::GRAY);
+    let mut pos = rect.min + vec2(10.0, 10.0);
+    if help.is_empty() {
+        ui.draw_text("click a system or ship for the rules that apply to it (H hides this)", pos, colors::GRAY);
+        return;
+    }
+    let title = match selected{
+        Some(ship) => format!("the {ship} ship"),
+        None => "this system".to_string(),
+    };
+    ui.draw_text(&format!("what you can do with {title} (H hides this)"), pos, colors::WHITE);
+    pos.y += 22.0;
+    let width = rect.width() - 30.0;
+    let line_height = 16.0;
+    for entry in help{
+        let (status, color) = match &entry.actions{
+            Ok(actions) => {
+                let turns: Vec<String> = actions.iter().map(|actions|logic::format_turn(actions)).collect();
+                let via = if entry.by_sacrifice { ", by sacrificing a ship" } else { "" };
+                let ways = match actions.len(){
+                    1 => "one way".to_string(),
+                    count => format!("{count} ways"),
+                };
+                (format!("{ways}{via}: {}", turns.join(", ")), colors::WHITE)
+            },
+            Err(blocked) => (format!("blocked: {blocked}"), colors::ORANGE),
+        };
+        if pos.y > rect.max.y - line_height {
+            return;
+        }
+        ui.draw_text(entry.name, pos, color);
+        pos.y += line_height;
+        // the rule once, then what it means here, cut off when it gets long
+        let lines = wrap(ui, entry.rule, width).into_iter().map(|line|(line, colors::GRAY))
+            .chain(wrap(ui, &status, width).into_iter().take(2).map(|line|(line, color)));
+        for (line, color) in lines{
+            if pos.y > rect.max.y - line_height {
+                return;
+            }
+            ui.draw_text(&line, pos + vec2(16.0, 0.0), color);
+            pos.y += line_height;
+        }
+        pos.y += 4.0;
+    }
+}
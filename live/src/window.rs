@@ -0,0 +1,781 @@
+//! the game window, everything that needs raylib
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::graphics::*;
+use crate::{logic, view, players};
+use crate::clock::{Clock, TimeControl, TimeSource};
+use crate::players::{Played, Seat};
+
+impl TimeSource for DrawHandle<'_> {
+    fn now(&self)->f64{
+        self.time()
+    }
+}
+
+pub struct State<'g>{
+    g: &'g mut Graphics,
+    sdf_shader: Shader,
+    font: SdfFont,
+    model: Model,
+}
+
+pub struct Ui<'a, 'b, 'c>{
+    g: &'c mut DrawHandle<'a>,
+    pub(crate) font: SdfFont,
+    sdf_shader: &'b Shader,
+    pub(crate) text_scale: f32,
+}
+impl<'a, 'b, 'c> Ui<'a, 'b, 'c>{
+    pub fn draw_text(&mut self, text: &str, pos: Vec2, color: Color){
+        //self.g.g.draw_text(&self.font, text, pos, self.text_scale, color);
+        self.g.draw_text_shader(self.font, self.sdf_shader, text, pos, self.text_scale, color)
+    }
+    /// true in the frame the button gets clicked
+    pub fn button(&mut self, rect: Rect, label: &str)->bool{
+        let hovered = rect.contains(self.mouse_pos());
+        let color = if hovered { colors::WHITE } else { colors::GRAY };
+        self.draw_rect_lines(rect, 1.0, color);
+        let size = self.measure(self.font, label, self.text_scale);
+        self.draw_text(label, rect.center() - size / 2.0, color);
+        hovered && self.is_mouse_button_pressed(MouseButton::Left)
+    }
+    /// yes/no question in the middle of the window, `None` until one is clicked
+    pub fn confirm(&mut self, question: &str)->Option<bool>{
+        let window = Rect::from_min_size(Vec2::ZERO, self.window_size());
+        let size = self.measure(self.font, question, self.text_scale);
+        let panel = Rect::from_center_size(window.center(), size + vec2(40.0, 80.0));
+        self.draw_rect(panel, colors::BLACK);
+        self.draw_rect_lines(panel, 1.0, colors::WHITE);
+        self.draw_text(question, panel.min + vec2(20.0, 20.0), colors::WHITE);
+
+        let button = Rect::from_center_size(panel.center_bottom() - vec2(0.0, 24.0), vec2(60.0, 24.0));
+        if self.button(button.translate(vec2(-40.0, 0.0)), "yes") {
+            return Some(true);
+        }
+        if self.button(button.translate(vec2(40.0, 0.0)), "no") {
+            return Some(false);
+        }
+        None
+    }
+}
+impl<'a, 'b, 'c> std::ops::Deref for Ui<'a, 'b, 'c> {
+    type Target = DrawHandle<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.g
+    }
+}
+impl<'a, 'b, 'c> std::ops::DerefMut for Ui<'a, 'b, 'c> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.g
+    }
+}
+
+pub fn init(g: &mut Graphics)->State{
+
+    let font_data = FileData::load("DejaVuSansMono.ttf").unwrap();
+        
+    let font = g.font_from_file_sdf(&font_data, 100);
+
+    let shader = g.load_shader(None, "sdf.fs");
+
+    let model = init_model(&g);
+    State{
+        g,
+        sdf_shader: shader,
+        font,
+        model,
+    }
+}
+
+#[derive(Debug)]
+struct Model{
+    game: logic::Game,
+    /// one per player, started in `update` whenever they don't fit the game
+    seats: Vec<Seat>,
+    /// a whole turn entered in the window, for the local human to move
+    entered: Option<Vec<logic::Action>>,
+    clock: Clock,
+    /// look for an immediate loss before a local human's turn is played
+    blunder_check: bool,
+    /// a turn that loses at once and why, while the dialog asks whether to play it
+    confirm_turn: Option<(Vec<logic::Action>, String)>,
+    /// the resign button was clicked and waits for confirmation
+    confirm_resign: bool,
+    /// players that still have to pick a homeworld
+    setup_turns: u8,
+    /// homeworld choices for the player to move during setup
+    advice: Option<Vec<logic::HomeworldAdvice>>,
+    weights: logic::Weights,
+    /// compiled from the games in `GAMES_DIR`
+    book: Arc<logic::Book>,
+    /// the book panel replaces the board
+    show_book: bool,
+    /// the finished game was written to `GAMES_DIR`
+    saved: bool,
+    /// a turn being typed in the game notation, none while not typing
+    typing: Option<String>,
+    /// why the last typed turn or opened file was refused, or what failed to load or save
+    input_error: Option<String>,
+    /// the correspondence game being played and the file it is saved in
+    correspondence: Option<(String, logic::Correspondence)>,
+    /// the one correspondence turn of this session was played and saved
+    sent: bool,
+    /// the files in `CORRESPONDENCE_DIR` replace the board
+    show_files: bool,
+    /// every finished turn in words, see `logic::Narration`
+    log: logic::Narration,
+    /// the move log replaces the board
+    show_log: bool,
+    /// the system clicked on, and the ship if one was
+    selected: Option<(logic::SystemId, Option<logic::Id>)>,
+    /// what the player to move can do with the selection, below a smaller board
+    show_rules: bool,
+    /// the warnings' board and its threats, searching every opponent turn is too slow for each frame
+    threats: Option<(logic::Board, logic::Threats)>,
+}
+
+impl Model{
+    /// what the seat of `player` is called
+    fn name(&self, player: u8)->String{
+        self.seats.get(player as usize).map_or(format!("player {player}"), |seat|seat.name.clone())
+    }
+
+    fn names(&self)->Vec<String>{
+        self.seats.iter().map(|seat|seat.name.clone()).collect()
+    }
+
+    /// tells every seat's controller what just happened
+    fn observe(&mut self, played: Played){
+        for seat in &mut self.seats{
+            if let Err(error) = seat.controller.observe(&self.game, &played) {
+                self.input_error = Some(format!("{}: {error}", seat.name));
+            }
+        }
+    }
+
+    /// plays a whole turn for the player to move and ends it. A correspondence
+    /// turn is saved to its file right away, and only one is played per session.
+    fn play_turn(&mut self, turn: &[logic::Action])->Result<(), String>{
+        let player = self.game.board().current_player();
+        match &mut self.correspondence{
+            Some((path, game)) => {
+                if self.sent {
+                    return Err("you already played your turn, pass the file on".to_string());
+                }
+                let mut played = game.clone();
+                played.play(turn).ok_or("that turn can't be played")?;
+                save_correspondence(path, &played).map_err(|error|format!("{path}: {error}"))?;
+                self.game = played.game().clone();
+                *game = played;
+                self.sent = true;
+            },
+            None => {
+                self.game.board().legal_turn(turn).ok_or("that turn can't be played")?;
+                for action in turn{
+                    self.game.apply_action(*action);
+                }
+                self.game.end_turn();
+            },
+        }
+        // every player's first turn is their homeworld
+        if self.setup_turns > 0 {
+            self.setup_turns -= 1;
+            self.advice = None;
+        }
+        self.observe(Played::Turn(player, turn.to_vec()));
+        Ok(())
+    }
+
+    /// a typed line, a whole turn for the local human to move
+    fn enter_turn(&mut self, text: &str)->Result<(), String>{
+        let turn = logic::parse_turn(text).ok_or(format!("can't read `{text}`"))?;
+        self.game.board().legal_turn(&turn).ok_or("that turn can't be played")?;
+        self.entered = Some(turn);
+        Ok(())
+    }
+
+    /// a turn from the player to move, checked for blunders if it was made here
+    fn submit_turn(&mut self, turn: Vec<logic::Action>, local_human: bool){
+        let player = self.game.board().current_player();
+        let refutation = match local_human && self.blunder_check{
+            true => turn.iter()
+                .try_fold(self.game.board().clone(), |board, action|board.apply_action(*action))
+                .and_then(|after|after.blunder(player, &self.weights)),
+            false => None,
+        };
+        if let Some(refutation) = refutation {
+            self.confirm_turn = Some((turn, refutation.describe(&self.names())));
+        }else if let Err(error) = self.play_turn(&turn) {
+            self.input_error = Some(error);
+        }
+    }
+
+    fn decide(&mut self, player: u8, decision: logic::Decision){
+        match &mut self.correspondence{
+            Some((path, game)) => {
+                let mut decided = game.clone();
+                if decided.decide(player, decision).is_none() {
+                    return;
+                }
+                if let Err(error) = save_correspondence(path, &decided) {
+                    self.input_error = Some(format!("{path}: {error}"));
+                    return;
+                }
+                self.game = decided.game().clone();
+                *game = decided;
+            },
+            None => if self.game.decide(player, decision).is_none() {
+                return;
+            },
+        }
+        self.observe(Played::Decision(player, decision));
+    }
+
+    /// switches to the correspondence game saved at `path`
+    fn open_correspondence(&mut self, path: String){
+        let opened = std::fs::read_to_string(&path)
+            .map_err(|error|error.to_string())
+            .and_then(|text|logic::Correspondence::parse(&text));
+        let game = match opened{
+            Ok(game) => game,
+            Err(error) => {
+                self.input_error = Some(format!("{path}: {error}"));
+                return;
+            },
+        };
+        self.game = game.game().clone();
+        self.setup_turns = self.game.board().player_count() - self.game.board().systems().filter(|(_, system)|system.home.is_some()).count() as u8;
+        self.advice = None;
+        // only games that end in this session are added to the book
+        self.saved = self.game.outcome().is_some();
+        self.correspondence = Some((path, game));
+        self.sent = false;
+        self.show_files = false;
+        self.input_error = None;
+        self.log = logic::Narration::default();
+    }
+}
+
+/// finished games are saved here, and the opening book is compiled from them
+const GAMES_DIR: &str = "games";
+
+/// the book compiled from `GAMES_DIR`, what went wrong with its files goes to `errors`
+fn load_book(errors: &mut Vec<String>)->logic::Book{
+    match logic::Book::from_dir(GAMES_DIR){
+        Ok((book, skipped)) => {
+            if skipped > 0 {
+                errors.push(format!("{GAMES_DIR}: {skipped} files are not finished games"));
+            }
+            book
+        },
+        Err(_) => logic::Book::default(),
+    }
+}
+
+/// writes the record of `game` to a new file in `GAMES_DIR`, narrated
+/// for the players called `names`
+fn save_game(game: &logic::Game, names: &[String])->std::io::Result<()>{
+    std::fs::create_dir_all(GAMES_DIR)?;
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = format!("{GAMES_DIR}/{}.txt", since_epoch.as_millis());
+    std::fs::write(path, game.record().narrated(names))
+}
+
+const WEIGHTS_PATH: &str = "eval_weights.txt";
+
+/// evaluation weights from `WEIGHTS_PATH`, the defaults when it is missing or broken.
+/// Why it is broken goes to `errors`.
+fn load_weights(errors: &mut Vec<String>)->logic::Weights{
+    let text = match std::fs::read_to_string(WEIGHTS_PATH){
+        Ok(text) => text,
+        Err(_) => return logic::Weights::default(),
+    };
+    logic::Weights::parse(&text).unwrap_or_else(|error|{
+        errors.push(format!("{WEIGHTS_PATH}: {error}"));
+        logic::Weights::default()
+    })
+}
+
+/// the pieces new games are played with, like `rgy/123/2`, see `logic::PieceSet`.
+/// The standard set without it.
+const PIECES_PATH: &str = "pieces.txt";
+
+/// the piece set from `PIECES_PATH`, the standard one when it is missing or broken.
+/// Why it is broken goes to `errors`.
+fn load_piece_set(errors: &mut Vec<String>)->logic::PieceSet{
+    let Ok(text) = std::fs::read_to_string(PIECES_PATH) else {
+        return logic::PieceSet::STANDARD;
+    };
+    text.parse().unwrap_or_else(|error|{
+        errors.push(format!("{PIECES_PATH}: {error}"));
+        logic::PieceSet::STANDARD
+    })
+}
+
+/// the clock of new games, like `delay 300 5`, see `TimeControl`.
+/// Five minutes with a five second increment without it.
+const CLOCK_PATH: &str = "clock.txt";
+
+/// the time control from `CLOCK_PATH`, the default when it is missing or broken.
+/// Why it is broken goes to `errors`.
+fn load_time_control(errors: &mut Vec<String>)->TimeControl{
+    let default = TimeControl::Increment { initial: 5.0 * 60.0, increment: 5.0 };
+    let Ok(text) = std::fs::read_to_string(CLOCK_PATH) else {
+        return default;
+    };
+    text.parse().unwrap_or_else(|error|{
+        errors.push(format!("{CLOCK_PATH}: {error}"));
+        default
+    })
+}
+
+/// who plays which seat, see `players::parse_seats`. Everyone plays at this computer without it.
+const PLAYERS_PATH: &str = "players.txt";
+
+/// how long the engines think per turn in the window
+const ENGINE_TIME: Duration = Duration::from_secs(1);
+
+/// the seats from `PLAYERS_PATH` for a game of `player_count`
+fn load_seats(player_count: u8, weights: logic::Weights, book: &Arc<logic::Book>)->Result<Vec<Seat>, String>{
+    let Ok(text) = std::fs::read_to_string(PLAYERS_PATH) else {
+        return Ok(players::humans(player_count));
+    };
+    let seats = players::parse_seats(&text).map_err(|error|format!("{PLAYERS_PATH}: {error}"))?;
+    if seats.len() != player_count as usize {
+        return Err(format!("{PLAYERS_PATH}: {} seats for {player_count} players", seats.len()));
+    }
+    let limits = logic::Limits { time: Some(ENGINE_TIME), ..logic::Limits::default() };
+    players::start_seats(&seats, weights, book, limits).map_err(|error|format!("{PLAYERS_PATH}: {error}"))
+}
+
+/// turns without anything entering or leaving play before the game is a draw
+const NO_PROGRESS_LIMIT: u32 = 50;
+
+/// correspondence games, one file each, that players pass around between turns
+const CORRESPONDENCE_DIR: &str = "correspondence";
+
+/// the file names in `CORRESPONDENCE_DIR`, sorted
+fn correspondence_files()->Vec<String>{
+    let Ok(entries) = std::fs::read_dir(CORRESPONDENCE_DIR) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry|entry.ok()?.file_name().into_string().ok())
+        .collect();
+    files.sort();
+    files
+}
+
+/// writes next to `path` first so a crash never leaves half a file
+fn save_correspondence(path: &str, game: &logic::Correspondence)->std::io::Result<()>{
+    let temporary = format!("{path}.tmp");
+    std::fs::write(&temporary, game.to_string())?;
+    std::fs::rename(temporary, path)
+}
+
+/// starts a two player correspondence game in a new file in `CORRESPONDENCE_DIR`
+fn new_correspondence()->std::io::Result<String>{
+    std::fs::create_dir_all(CORRESPONDENCE_DIR)?;
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = format!("{CORRESPONDENCE_DIR}/{}.txt", since_epoch.as_millis());
+    save_correspondence(&path, &logic::Correspondence::new(2, Some(NO_PROGRESS_LIMIT)))?;
+    Ok(path)
+}
+
+fn init_model(g: &Graphics)->Model{
+    let player_count = 2;
+    let mut errors = Vec::new();
+    Model {
+        game: logic::Game::from_board(logic::Board::with_set(player_count, load_piece_set(&mut errors)), Some(NO_PROGRESS_LIMIT)),
+        seats: Vec::new(),
+        entered: None,
+        clock: Clock::new(load_time_control(&mut errors), player_count),
+        blunder_check: true,
+        confirm_turn: None,
+        confirm_resign: false,
+        setup_turns: player_count,
+        advice: None,
+        weights: load_weights(&mut errors),
+        book: Arc::new(load_book(&mut errors)),
+        show_book: false,
+        saved: false,
+        typing: None,
+        input_error: (!errors.is_empty()).then(||errors.join(", ")),
+        correspondence: None,
+        sent: false,
+        show_files: false,
+        log: logic::Narration::default(),
+        show_log: false,
+        selected: None,
+        show_rules: false,
+        threats: None,
+    }
+}
+
+/// `m:ss`, rounded up so a clock shows `0:00` only once it ran out
+fn format_clock(seconds: f64)->String{
+    let seconds = seconds.max(0.0).ceil() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+
+#[no_mangle]
+pub fn update(state: &mut State){
+    let text_scale = 15.0;
+    let model = &mut state.model;
+    let mut restart = false;
+
+    state.g.draw_frame(|g|{
+        g.clear_background(colors::BLACK);
+        let mut ui = Ui{
+            g,
+            font: state.font,
+            sdf_shader: &state.sdf_shader,
+            text_scale,
+        };
+        ui.draw_fps(10, 10);
+
+        let player_count = model.game.board().player_count();
+        if model.seats.len() != player_count as usize {
+            model.seats = load_seats(player_count, model.weights, &model.book).unwrap_or_else(|error|{
+                model.input_error = Some(error);
+                players::humans(player_count)
+            });
+        }
+        let over = model.game.outcome().is_some();
+        let current_player = model.game.board().current_player();
+        let local = model.seats[current_player as usize].controller.is_local_human();
+        let idle = !over && model.confirm_turn.is_none() && !model.confirm_resign && !model.sent;
+        // shortcuts are letters too, so they are off while typing
+        if let Some(text) = &mut model.typing {
+            text.extend(ui.chars_pressed().filter(|c|!c.is_control()));
+            if ui.is_key_pressed(Key::Backspace) {
+                text.pop();
+            }
+            if ui.is_key_pressed(Key::Tab) {
+                model.typing = None;
+            }else if ui.is_key_pressed(Key::Enter) {
+                let text = text.clone();
+                match model.enter_turn(&text){
+                    Ok(()) => {
+                        model.typing = None;
+                        model.input_error = None;
+                    },
+                    Err(error) => model.input_error = Some(error),
+                }
+            }
+        }else{
+            if ui.is_key_pressed(Key::B) {
+                model.blunder_check = !model.blunder_check;
+            }
+            if ui.is_key_pressed(Key::W) {
+                let mut errors = Vec::new();
+                model.weights = load_weights(&mut errors);
+                if !errors.is_empty() {
+                    model.input_error = Some(errors.join(", "));
+                }
+            }
+            if ui.is_key_pressed(Key::O) {
+                model.show_book = !model.show_book;
+            }
+            if ui.is_key_pressed(Key::C) {
+                model.show_files = !model.show_files;
+            }
+            if ui.is_key_pressed(Key::L) {
+                model.show_log = !model.show_log;
+            }
+            if ui.is_key_pressed(Key::H) {
+                model.show_rules = !model.show_rules;
+            }
+            if idle && local && model.setup_turns == 0 && ui.is_key_pressed(Key::Enter) {
+                model.typing = Some(String::new());
+            }
+        }
+        if model.game.outcome().is_some() && !model.saved {
+            if let Err(error) = save_game(&model.game, &model.names()) {
+                model.input_error = Some(format!("saving the game failed: {error}"));
+            }
+            model.saved = true;
+        }
+        // the local human's own input
+        let your_turn = idle && local && model.setup_turns == 0 && model.typing.is_none();
+        // Space passes, a correspondence turn is always typed so one isn't sent by accident
+        if your_turn && model.correspondence.is_none() && ui.is_key_pressed(Key::Space) {
+            model.entered = Some(Vec::new());
+        }
+        // correspondence games take days, they go without a clock
+        if !over && model.correspondence.is_none() {
+            model.clock.update(model.game.board().current_player(), &*ui);
+            if let Some(player) = model.clock.flagged() {
+                model.game.time_out(player);
+            }
+        }
+        let board = model.game.board();
+        if model.threats.as_ref().is_none_or(|(seen, _)|seen != board) {
+            model.threats = Some((board.clone(), board.threats(board.current_player())));
+        }
+
+        let powers = board.power_summary();
+        for player in 0..board.player_count(){
+            let remaining = model.clock.remaining(player, &*ui);
+            let color = if player == board.current_player() { colors::WHITE } else { colors::GRAY };
+            let clock = if model.game.is_out(player) { "out".to_string() } else { format_clock(remaining) };
+            let text = format!("{:<10.10} {clock}", model.seats[player as usize].name);
+            let pos = vec2(10.0, 40.0 + 20.0 * player as f32);
+            ui.draw_text(&text, pos, color);
+            view::draw_powers(&mut ui, powers[player as usize], pos + vec2(140.0, 0.0));
+        }
+        let mut y = 40.0 + 20.0 * board.player_count() as f32;
+        if board.set() != logic::PieceSet::STANDARD {
+            ui.draw_text(&format!("played with {}, {} pieces", board.set(), board.set().len()), vec2(10.0, y), colors::GRAY);
+            y += 20.0;
+        }
+        if let Some(outcome) = model.game.outcome() {
+            ui.draw_text(&outcome.to_string(), vec2(10.0, y), colors::RED);
+            y += 20.0;
+        }
+
+        let mut warnings = Vec::new();
+        let threats = model.threats.as_ref().map(|(_, threats)|threats).expect("set above");
+        if !threats.attackable.is_empty() {
+            warnings.push(format!("{} of your ships can be captured", threats.attackable.len()));
+        }
+        if threats.homeworld_catastrophe() {
+            warnings.push("an opponent can cause a catastrophe at your homeworld".to_string());
+        }
+        let elsewhere = threats.catastrophes.iter().filter(|(system, _)|Some(*system) != threats.homeworld).count();
+        if elsewhere > 0 {
+            warnings.push(format!("an opponent can cause a catastrophe in {elsewhere} of your systems"));
+        }
+        if !threats.assault.is_empty() {
+            warnings.push(format!("{} enemy ships can reach your homeworld", threats.assault.len()));
+        }
+        for warning in warnings{
+            ui.draw_text(&warning, vec2(10.0, y), colors::ORANGE);
+            y += 20.0;
+        }
+        let blunder_check = if model.blunder_check { "on" } else { "off" };
+        ui.draw_text(&format!("blunder check: {blunder_check} (B)"), vec2(10.0, y), colors::GRAY);
+        y += 20.0;
+        ui.draw_text("opening book (O)", vec2(10.0, y), colors::GRAY);
+        y += 20.0;
+        ui.draw_text("move log (L)", vec2(10.0, y), colors::GRAY);
+        y += 20.0;
+        ui.draw_text("rules help (H)", vec2(10.0, y), colors::GRAY);
+        y += 20.0;
+        match &model.correspondence{
+            Some((path, game)) => {
+                ui.draw_text(&format!("{path} (C)"), vec2(10.0, y), colors::GRAY);
+                y += 20.0;
+                // players compare this to be sure no turns were cut off the file
+                ui.draw_text(&format!("chain {:016x}", game.head()), vec2(10.0, y), colors::GRAY);
+                if model.sent {
+                    y += 20.0;
+                    let next = model.name(board.current_player());
+                    ui.draw_text(&format!("saved, pass it on to {next}"), vec2(10.0, y), colors::WHITE);
+                }
+            },
+            None => ui.draw_text("correspondence games (C)", vec2(10.0, y), colors::GRAY),
+        }
+        y += 20.0;
+        if let Some(error) = &model.input_error {
+            ui.draw_text(error, vec2(10.0, y), colors::ORANGE);
+            y += 20.0;
+        }
+        y += 10.0;
+
+        if model.setup_turns == 0 {
+            let area = match model.show_rules{
+                true => Rect::new(260.0, 40.0, 520.0, 280.0),
+                false => Rect::new(260.0, 40.0, 520.0, 540.0),
+            };
+            let panels = model.show_book || model.show_log || model.show_files;
+            if model.show_rules && !panels && ui.is_mouse_button_pressed(MouseButton::Left) && area.contains(ui.mouse_pos()) {
+                model.selected = view::pick(board, area, ui.mouse_pos());
+            }
+            // ships move and systems go away, a selection that did is dropped
+            model.selected = model.selected.filter(|(system, ship)|board.system(*system)
+                .is_some_and(|system|ship.is_none_or(|ship|system.ships.iter().flatten().any(|other|*other == ship))));
+            let selected = if model.show_rules { model.selected } else { None };
+            view::draw_board(&mut ui, board, area, selected);
+            if model.show_rules {
+                let help = selected.map_or(Vec::new(), |(system, ship)|board.rules_help(system, ship));
+                view::draw_rules(&mut ui, selected.and_then(|(_, ship)|ship), &help, Rect::new(260.0, 324.0, 520.0, 256.0));
+            }
+
+            let player = board.current_player();
+            view::draw_eval_bar(&mut ui, board.advantage(0, &model.weights), Rect::new(784.0, 40.0, 12.0, 540.0));
+            let eval = board.evaluate(player, &model.weights);
+            ui.draw_text(&format!("evaluation {:+.1} (W reloads)", eval.score), vec2(10.0, y), colors::GRAY);
+            for (feature, value, score) in eval.breakdown(&model.weights){
+                y += 20.0;
+                ui.draw_text(&format!("{:<21}{value:>3}{score:>+6.1}", feature.name()), vec2(10.0, y), colors::GRAY);
+            }
+        }
+
+        if model.setup_turns > 0 && idle && local && !model.show_book && !model.show_files {
+            let player = board.current_player();
+            let name = model.name(player);
+            let advice = model.advice.get_or_insert_with(||board.homeworld_advice(player));
+            ui.draw_text(&format!("{name}, choose your homeworld"), vec2(300.0, 40.0), colors::WHITE);
+            if let Some(turn) = model.book.best(board, 1) {
+                let turn: Vec<String> = turn.iter().map(|action|action.to_string()).collect();
+                ui.draw_text(&format!("book: {}", turn.join("; ")), vec2(300.0, 58.0), colors::GRAY);
+            }
+            let mut chosen = None;
+            for (i, advice) in advice.iter().take(8).enumerate(){
+                let y = 80.0 + 50.0 * i as f32;
+                let label = format!("{} + {} stars, {} ship  ({})", advice.stars[0], advice.stars[1], advice.ship, advice.score);
+                if ui.button(Rect::new(300.0, y, 420.0, 24.0), &label) {
+                    chosen = Some(logic::Action::CreateHomeworld { stars: advice.stars, ship: advice.ship });
+                }
+                if let Some(risk) = advice.risks.first() {
+                    ui.draw_text(risk, vec2(300.0, y + 26.0), colors::ORANGE);
+                }
+            }
+            if let Some(action) = chosen {
+                model.entered = Some(vec![action]);
+            }
+        }
+
+        if model.show_book {
+            let continuations = model.book.continuations(model.game.board());
+            view::draw_book(&mut ui, &continuations, Rect::new(260.0, 40.0, 520.0, 540.0));
+        }
+        if model.show_log {
+            let names = model.names();
+            model.log.update(&model.game.record(), &names);
+            view::draw_log(&mut ui, model.log.lines(), Rect::new(260.0, 40.0, 520.0, 540.0));
+        }
+        if model.show_files {
+            let files = correspondence_files();
+            match view::draw_files(&mut ui, &files, Rect::new(260.0, 40.0, 520.0, 540.0)){
+                Some(Some(i)) => model.open_correspondence(format!("{CORRESPONDENCE_DIR}/{}", files[i])),
+                Some(None) => match new_correspondence(){
+                    Ok(path) => model.open_correspondence(path),
+                    Err(error) => model.input_error = Some(format!("{CORRESPONDENCE_DIR}: {error}")),
+                },
+                None => {},
+            }
+        }
+        if let Some(text) = &model.typing {
+            ui.draw_text(&format!("turn> {text}_  (Enter plays, Tab cancels)"), vec2(260.0, 584.0), colors::WHITE);
+        }else if your_turn {
+            ui.draw_text("Enter: type a turn", vec2(260.0, 584.0), colors::GRAY);
+        }else if idle && !local {
+            let name = model.name(current_player);
+            let text = match model.seats[current_player as usize].controller.progress(){
+                Some(search) => {
+                    let pv: Vec<String> = search.pv.iter().map(|turn|logic::format_turn(turn)).collect();
+                    format!("{name} is thinking: depth {}, {}/{} turns, {} positions, best {} ({:+.1})",
+                        search.depth, search.scored, search.total, search.nodes, pv.join(" | "), search.score)
+                },
+                None => format!("{name} is thinking"),
+            };
+            ui.draw_text(&text, vec2(260.0, 584.0), colors::GRAY);
+        }
+
+        if idle {
+            for player in 0..player_count{
+                let seat = &mut model.seats[player as usize];
+                match seat.controller.poll_decisions(){
+                    Ok(decisions) => for decision in decisions{
+                        model.decide(player, decision);
+                    },
+                    Err(error) => model.input_error = Some(format!("{}: {error}", seat.name)),
+                }
+            }
+        }
+        // the player to move may have resigned just now
+        if idle && model.game.outcome().is_none() {
+            let seat = &mut model.seats[current_player as usize];
+            match seat.controller.poll_turn(&model.game, &mut model.entered){
+                Ok(Some(turn)) => model.submit_turn(turn, local),
+                Ok(None) => {},
+                Err(error) => {
+                    model.input_error = Some(format!("{}: {error}", seat.name));
+                    model.decide(current_player, logic::Decision::Resign);
+                },
+            }
+        }
+        // whatever a human entered out of turn is dropped
+        model.entered = None;
+
+        let buttons = vec2(10.0, 560.0);
+        if your_turn && model.game.board().current_player() == current_player {
+            match model.game.draw_offer(){
+                Some(offerer) if offerer != current_player => {
+                    ui.draw_text(&format!("{} offers a draw", model.name(offerer)), buttons - vec2(0.0, 24.0), colors::WHITE);
+                    if ui.button(Rect::from_min_size(buttons, vec2(110.0, 24.0)), "accept draw") {
+                        model.decide(current_player, logic::Decision::AcceptDraw);
+                    }
+                    if ui.button(Rect::from_min_size(buttons + vec2(120.0, 0.0), vec2(110.0, 24.0)), "decline draw") {
+                        model.decide(current_player, logic::Decision::DeclineDraw);
+                    }
+                },
+                Some(_) => ui.draw_text("draw offered", buttons + vec2(0.0, 4.0), colors::GRAY),
+                None => if ui.button(Rect::from_min_size(buttons, vec2(110.0, 24.0)), "offer draw") {
+                    model.decide(current_player, logic::Decision::OfferDraw);
+                },
+            }
+            if ui.button(Rect::from_min_size(buttons + vec2(0.0, 30.0), vec2(110.0, 24.0)), "resign") {
+                model.confirm_resign = true;
+            }
+        }
+        if over && ui.button(Rect::from_min_size(buttons + vec2(0.0, 30.0), vec2(110.0, 24.0)), "new game") {
+            restart = true;
+        }
+
+        if let Some((turn, refutation)) = &model.confirm_turn {
+            match ui.confirm(&format!("this loses at once:\n{refutation}\nplay the turn anyway?")){
+                Some(true) => {
+                    let turn = turn.clone();
+                    model.confirm_turn = None;
+                    if let Err(error) = model.play_turn(&turn) {
+                        model.input_error = Some(error);
+                    }
+                },
+                Some(false) => model.confirm_turn = None,
+                None => {},
+            }
+        }
+        if model.confirm_resign {
+            let player = model.game.board().current_player();
+            match ui.confirm(&format!("{}, resign the game?", model.name(player))){
+                Some(true) => {
+                    model.decide(player, logic::Decision::Resign);
+                    model.confirm_resign = false;
+                },
+                Some(false) => model.confirm_resign = false,
+                None => {},
+            }
+        }
+
+    });
+    if restart {
+        state.model = init_model(state.g);
+    }
+}
+
+
+#[no_mangle]
+pub fn should_close(state: &mut State)->bool{
+    state.g.window_should_close()
+}
+
+#[no_mangle]
+pub fn should_reload(state: &mut State)->bool{
+    let reload = state.g.is_key_pressed(Key::R) && state.model.typing.is_none();
+    if reload {
+        // the controllers' code is in this library, the next one starts them again
+        state.model.seats.clear();
+    }
+    reload
+}